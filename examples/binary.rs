@@ -0,0 +1,60 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use slog::Drain;
+
+use slog_conf::{BinaryConfig, Config};
+use slog_conf::common::Target;
+use slog_conf::binary::Reader;
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let config: Box<Config> = toml::from_str(include_str!("binary.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- BinaryConfig ----------");
+    println!("{:#?}\n", config.downcast_ref::<BinaryConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- BinaryConfig as TOML --");
+    println!("{}\n", out);
+
+    let path = match config.downcast_ref::<BinaryConfig>().unwrap().target {
+        Target::File { ref path, .. } => path.clone(),
+        _ => unreachable!(),
+    };
+
+    {
+        // build a logger
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        // use the logger
+        warn!(log, "a warning"; "a" => "b");
+        info!(log, "some information that might be relevant"; "b" => 1, "c" => -2.5);
+
+        let log = log.new(o!("a" => "b"));
+        debug!(log, "a debug message"; "f" => true);
+        debug!(log, "another debug message"; "g" => None as Option<u32>);
+        error!(log, "an error message");
+        error!(log, #"test", "a tagged error message");
+
+        // the guard is dropped here, flushing all records
+    }
+
+    // read the records back and convert them to JSON
+    println!("-- Records as JSON -------");
+    for record in Reader::open(&path).unwrap() {
+        println!("{}", record.unwrap().to_json());
+    }
+}
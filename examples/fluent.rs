@@ -0,0 +1,60 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io::Read;
+use std::net::TcpListener;
+use std::thread;
+
+use slog::Drain;
+
+use slog_conf::{Config, FluentConfig};
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let config: Box<Config> = toml::from_str(include_str!("fluent.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- FluentConfig ----------");
+    println!("{:#?}\n", config.downcast_ref::<FluentConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- FluentConfig as TOML --");
+    println!("{}\n", out);
+
+    // listen on a socket standing in for Fluentd
+    let fluentd = TcpListener::bind("127.0.0.1:24224").unwrap();
+    let received = thread::spawn(move || {
+        let (mut stream, _) = fluentd.accept().unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        buf
+    });
+
+    // build a logger and use it
+    {
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        warn!(log, "a warning"; "a" => "b");
+        info!(log, "some information that might be relevant"; "b" => "c");
+
+        let log = log.new(o!("a" => "b"));
+        debug!(log, "a debug message"; "f" => "g");
+        error!(log, #"tag", "an error message");
+    }
+
+    // show what Fluentd has received (MessagePack, printed as escaped string)
+    println!("-- Received --------------");
+    let buf = received.join().unwrap();
+    println!("{:?}", String::from_utf8_lossy(&buf));
+}
@@ -0,0 +1,56 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use slog::Drain;
+
+use slog_conf::{Config, GelfConfig};
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let config: Box<Config> = toml::from_str(include_str!("gelf.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- GelfConfig ------------");
+    println!("{:#?}\n", config.downcast_ref::<GelfConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- GelfConfig as TOML ----");
+    println!("{}\n", out);
+
+    // bind a socket standing in for Graylog
+    let graylog = UdpSocket::bind("127.0.0.1:12201").unwrap();
+    graylog.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+    // build a logger and use it
+    {
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        warn!(log, "a warning"; "a" => "b");
+        info!(log, "some information that might be relevant"; "b" => "c");
+
+        let log = log.new(o!("a" => "b"));
+        debug!(log, "a debug message"; "f" => "g");
+        error!(log, #"tag", "an error message\nwith some details");
+    }
+
+    // show what Graylog has received
+    println!("-- Received --------------");
+    let mut buf = [0; 8192];
+    while let Ok(len) = graylog.recv(&mut buf) {
+        println!("{}", String::from_utf8_lossy(&buf[..len]));
+    }
+}
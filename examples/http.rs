@@ -0,0 +1,97 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use slog::Drain;
+
+use slog_conf::{Config, HttpConfig};
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let config: Box<Config> = toml::from_str(include_str!("http.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- HttpConfig ------------");
+    println!("{:#?}\n", config.downcast_ref::<HttpConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- HttpConfig as TOML ----");
+    println!("{}\n", out);
+
+    // listen on a socket standing in for Loki
+    let loki = TcpListener::bind("127.0.0.1:3100").unwrap();
+    let server = thread::spawn(move || {
+        println!("-- Received --------------");
+
+        for stream in loki.incoming() {
+            if !handle(stream.unwrap()) {
+                break;
+            }
+        }
+    });
+
+    // build a logger and use it
+    {
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        warn!(log, "a warning"; "a" => "b");
+        info!(log, "some information that might be relevant"; "b" => "c");
+
+        let log = log.new(o!("a" => "b"));
+        debug!(log, "a debug message"; "f" => "g");
+        error!(log, #"tag", "an error message");
+    }
+
+    // the logger has sent all records, stop the server with an empty request
+    drop(TcpStream::connect("127.0.0.1:3100").unwrap());
+    server.join().unwrap();
+}
+
+/// Prints a single request and responds with `204 No Content`.
+///
+/// Returns `false` if the request is empty.
+fn handle(stream: TcpStream) -> bool {
+    let mut reader = BufReader::new(stream);
+    let mut length = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 {
+            return false;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if line.to_lowercase().starts_with("content-length:") {
+            length = line["content-length:".len()..].trim().parse().unwrap();
+        }
+
+        println!("{}", line);
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    println!("\n{}\n", String::from_utf8_lossy(&body));
+
+    let mut stream = reader.into_inner();
+    stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+
+    true
+}
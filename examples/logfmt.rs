@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use slog::Drain;
+
+use slog_conf::{Config, LogfmtConfig};
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let config: Box<Config> = toml::from_str(include_str!("logfmt.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- LogfmtConfig ----------");
+    println!("{:#?}\n", config.downcast_ref::<LogfmtConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- LogfmtConfig as TOML --");
+    println!("{}\n", out);
+
+    // build a logger
+    let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+    let log = slog::Logger::root(async.fuse(), o!());
+
+    // use the logger
+    warn!(log, "a warning"; "a" => "b");
+    info!(log, "some information that might be relevant"; "b" => "c");
+
+    let log = log.new(o!("a" => "b"));
+    debug!(log, "a debug message"; "f" => "g");
+    debug!(log, "another debug message");
+    error!(log, "an error message");
+    error!(log, #"test", "a tagged error message");
+    info!(log, "a message with \"quotes\""; "path" => "C:\\Program Files", "lines" => "a\nb", "empty" => "");
+}
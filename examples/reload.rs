@@ -0,0 +1,72 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_async;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::path::Path;
+use std::time::Duration;
+
+use slog::Drain;
+
+use slog_conf::{Config, Error};
+use slog_conf::reload::{self, Reload};
+
+
+const INITIAL: &str = r#"
+type = "term"
+target = "stderr"
+level = "info"
+"#;
+
+const CHANGED: &str = r#"
+type = "term"
+target = "stderr"
+level = "debug"
+"#;
+
+const INVALID: &str = r#"
+type = "term"
+target = "somewhere"
+"#;
+
+
+// load a configuration with the default deserializers
+fn load(path: &Path) -> Result<Box<Config>, Error> {
+    let data = std::fs::read_to_string(path)?;
+    toml::from_str(&data).map_err(|e| Error::msg(&e))
+}
+
+fn main() {
+    let path = std::env::temp_dir().join("slog-conf").join("reload.toml");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, INITIAL).unwrap();
+
+    // check the file for changes every 100ms
+    let options = Reload {
+        interval: Some(Duration::from_millis(100).into()),
+        signal: false,
+    };
+
+    // build a reloadable drain and wrap it into an async drain
+    let (drain, _watcher) = reload::watch(&path, options, load).unwrap();
+    let (drain, _guard) = slog_async::Async::new(drain.fuse()).build_with_guard();
+    let log = slog::Logger::root(drain.fuse(), o!());
+
+    info!(log, "initial configuration loaded");
+    debug!(log, "this debug message is not shown");
+
+    // change the configuration, the logger keeps working
+    std::fs::write(&path, CHANGED).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    info!(log, "changed configuration loaded");
+    debug!(log, "this debug message is shown");
+
+    // an invalid configuration keeps the current one and logs an error
+    std::fs::write(&path, INVALID).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    debug!(log, "this debug message is still shown");
+}
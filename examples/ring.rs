@@ -0,0 +1,41 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io::{self, Write};
+
+use slog::Drain;
+
+use slog_conf::Config;
+use slog_conf::output;
+
+
+const CONFIG: &str = r#"
+type = "json"
+target = { ring = "/tmp/slog-conf.ring", size = "1KiB" }
+level = "debug"
+"#;
+
+
+fn main() {
+    let config: Box<Config> = toml::from_str(CONFIG).unwrap();
+
+    {
+        // build a logger writing to a ring file of 1KiB
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        // write more records than fit into the ring, overwriting the oldest
+        for i in 0..20 {
+            info!(log, "record"; "i" => i);
+        }
+
+        // the guard is dropped here, flushing all records
+    }
+
+    // dump the records remaining in the ring in chronological order
+    let records = output::read_ring("/tmp/slog-conf.ring").unwrap();
+    io::stdout().write_all(&records).unwrap();
+}
@@ -0,0 +1,69 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use slog::Drain;
+
+use slog_conf::{Config, SpoolConfig};
+
+
+fn main() {
+    // load a configuration with the default deserializers
+    let mut config: Box<Config> = toml::from_str(include_str!("spool.toml")).unwrap();
+
+    // show what we have just loaded
+    println!("-- SpoolConfig -------------");
+    println!("{:#?}\n", config.downcast_ref::<SpoolConfig>().unwrap());
+
+    // show it again, this time as toml output (via serialization)
+    // Note: we have to use an intermediate `toml::Value` here because TOML
+    // requires that tables (i.e. maps, structs, or sequences) must be emitted
+    // last. We can acheive this with an intermediate `toml::Value`. This
+    // should not be necessary for other formats.
+    let val = toml::Value::try_from(&*config).unwrap();
+    let out = toml::to_string_pretty(&val).unwrap();
+    println!("-- SpoolConfig as TOML -----");
+    println!("{}\n", out);
+
+    {
+        // build a logger
+        let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+        let log = slog::Logger::root(async.fuse(), o!());
+
+        // use the logger, all records end up in the spool
+        warn!(log, "a warning"; "a" => "b");
+        info!(log, "some information that might be relevant"; "b" => "c");
+
+        let log = log.new(o!("a" => "b"));
+        debug!(log, "a debug message"; "f" => "g");
+        debug!(log, "another debug message");
+    }
+
+    let path = config.downcast_ref::<SpoolConfig>().unwrap().path.clone();
+
+    println!("-- Spool -------------------");
+    let mut entries: Vec<_> = fs::read_dir(&path).unwrap().map(|e| e.unwrap()).collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let len = entry.metadata().unwrap().len();
+        println!("{} ({} bytes)", entry.file_name().to_string_lossy(), len);
+    }
+    println!();
+
+    // "restart" with a working nested logger, the spool is replayed at startup
+    config.downcast_mut::<SpoolConfig>().unwrap().drain =
+        toml::from_str("type = \"json\"\nlevel = \"debug\"").unwrap();
+
+    println!("-- Replayed ----------------");
+    let (async, _guard) = slog_conf::build(config.as_ref()).unwrap();
+    let log = slog::Logger::root(async.fuse(), o!());
+
+    thread::sleep(Duration::from_millis(500));
+    error!(log, "an error message after the replay");
+}
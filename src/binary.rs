@@ -0,0 +1,565 @@
+//! Compact binary records written by loggers of type `binary` and a reader
+//! for them.
+//!
+//! Each record is written as its length (4 bytes, big-endian) followed by a
+//! MessagePack or CBOR map with the following entries:
+//!
+//! - `ts`: the time of the record in nanoseconds since the UNIX epoch.
+//! - `level`: the level of the record, e.g. `"info"`.
+//! - `msg`: the message of the record.
+//! - `tag`: the tag of the record, omitted if empty.
+//! - `module`, `file`, `line`: the location of the record.
+//! - `kv`: a map containing the key-value pairs of the record followed by the
+//!   key-value pairs of the logger, with their types preserved. Nested values
+//!   (see `slog::SerdeValue`) are stored as nested arrays and maps.
+//!
+//! The encoding is detected per record, thus a [`Reader`](Reader) does not
+//! need to know with which encoding a file has been written.
+//!
+//! ```no_run
+//! use slog_conf::binary::Reader;
+//!
+//! for record in Reader::open("trace.bin").unwrap() {
+//!     println!("{}", record.unwrap().to_json());
+//! }
+//! ```
+
+use cbor;
+use msgpack;
+use common::Level;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use slog::{self, Key, OwnedKVList, SerdeValue, KV};
+
+use chrono::{DateTime, Utc};
+
+
+/// The binary encoding of records.
+///
+/// Defaults to [`MessagePack`](Encoding::MessagePack).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    /// MessagePack.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+
+    /// CBOR (RFC 7049).
+    #[serde(rename = "cbor")]
+    Cbor,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::MessagePack
+    }
+}
+
+
+/// A typed value of a key-value pair.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A unit, `None`, or `null` value.
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Converts this value to a JSON value.
+    ///
+    /// Byte strings are converted to arrays of numbers, non-finite floats to
+    /// `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+
+        match *self {
+            Value::Null => Json::Null,
+            Value::Bool(v) => Json::Bool(v),
+            Value::Int(v) => v.into(),
+            Value::Uint(v) => v.into(),
+            Value::Float(v) => v.into(),
+            Value::Str(ref v) => v.clone().into(),
+            Value::Bytes(ref v) => v.clone().into(),
+            Value::Array(ref v) => Json::Array(v.iter().map(Value::to_json).collect()),
+            Value::Map(ref v) => {
+                Json::Object(v.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+            },
+        }
+    }
+
+    fn from_json(value: serde_json::Value) -> Value {
+        use serde_json::Value as Json;
+
+        match value {
+            Json::Null => Value::Null,
+            Json::Bool(v) => Value::Bool(v),
+            Json::Number(ref n) if n.is_u64() => Value::Uint(n.as_u64().unwrap_or_default()),
+            Json::Number(ref n) if n.is_i64() => Value::Int(n.as_i64().unwrap_or_default()),
+            Json::Number(n) => Value::Float(n.as_f64().unwrap_or_default()),
+            Json::String(v) => Value::Str(v),
+            Json::Array(v) => Value::Array(v.into_iter().map(Value::from_json).collect()),
+            Json::Object(v) => {
+                Value::Map(v.into_iter().map(|(k, v)| (k, Value::from_json(v))).collect())
+            },
+        }
+    }
+}
+
+
+/// A decoded record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// The time at which the record has been logged.
+    pub time: DateTime<Utc>,
+
+    /// The level of the record.
+    pub level: Level,
+
+    /// The message of the record.
+    pub msg: String,
+
+    /// The tag of the record, empty if none.
+    pub tag: String,
+
+    /// The module in which the record has been logged.
+    pub module: String,
+
+    /// The file in which the record has been logged.
+    pub file: String,
+
+    /// The line at which the record has been logged.
+    pub line: u32,
+
+    /// The key-value pairs of the record followed by the key-value pairs of
+    /// the logger.
+    pub kv: Vec<(String, Value)>,
+}
+
+impl Record {
+    /// Converts this record to a JSON object in the `tagged` format of the
+    /// `json` type, i.e. with the keys `ts`, `level`, `msg`, and `tag` (if
+    /// not empty), followed by the key-value pairs.
+    ///
+    /// If a key occurs more than once, the first pair takes precedence.
+    pub fn to_json(&self) -> serde_json::Value {
+        let level: slog::Level = self.level.into();
+
+        let mut obj = serde_json::Map::new();
+        obj.insert("ts".into(), self.time.to_rfc3339().into());
+        obj.insert("level".into(), level.as_short_str().into());
+        obj.insert("msg".into(), self.msg.clone().into());
+
+        if !self.tag.is_empty() {
+            obj.insert("tag".into(), self.tag.clone().into());
+        }
+
+        for (key, value) in &self.kv {
+            if !obj.contains_key(key) {
+                obj.insert(key.clone(), value.to_json());
+            }
+        }
+
+        serde_json::Value::Object(obj)
+    }
+}
+
+
+/// An iterator over the records of a file or stream written by a logger of
+/// type `binary`.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens the file at the specified path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Reader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader reading records from the specified reader.
+    pub fn new(inner: R) -> Self {
+        Reader { inner }
+    }
+
+    /// Unwraps this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        let mut len = [0; 4];
+        let mut filled = 0;
+
+        // distinguish the end of the stream from a truncated record
+        while filled < len.len() {
+            match self.inner.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(truncated()),
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        let len = u64::from(u32::from_be_bytes(len));
+        let mut payload = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut payload)?;
+
+        if payload.len() as u64 != len {
+            return Err(truncated());
+        }
+
+        decode(&payload).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+
+/// Decodes a single record without its length prefix, detecting its
+/// encoding.
+pub fn decode(mut payload: &[u8]) -> io::Result<Record> {
+    let value = match payload.first() {
+        // a MessagePack map (fixmap, map 16, or map 32)
+        Some(&b) if b & 0xf0 == 0x80 || b == 0xde || b == 0xdf => {
+            from_msgpack(msgpack::read(&mut payload)?)?
+        },
+        // a CBOR map with definite length
+        Some(&b) if b >> 5 == 5 => from_cbor(cbor::read(&mut payload)?)?,
+        _ => return Err(invalid("invalid binary record")),
+    };
+
+    let mut entries = match value {
+        Value::Map(entries) => entries,
+        _ => return Err(invalid("invalid binary record")),
+    };
+
+    let mut take = |key: &str| {
+        entries
+            .iter()
+            .position(|(k, _)| k == key)
+            .map(|pos| entries.swap_remove(pos).1)
+    };
+
+    let string = |value: Option<Value>| match value {
+        Some(Value::Str(s)) => Ok(s),
+        None => Ok(String::new()),
+        _ => Err(invalid("invalid string in binary record")),
+    };
+
+    let ts = match take("ts") {
+        Some(Value::Int(ts)) => ts,
+        Some(Value::Uint(ts)) if ts <= i64::MAX as u64 => ts as i64,
+        _ => return Err(invalid("invalid timestamp in binary record")),
+    };
+
+    let level = match take("level") {
+        Some(Value::Str(ref level)) => level.parse::<slog::Level>().ok().map(Level::from),
+        _ => None,
+    };
+
+    let line = match take("line") {
+        Some(Value::Uint(line)) => line as u32,
+        _ => 0,
+    };
+
+    let kv = match take("kv") {
+        Some(Value::Map(kv)) => kv,
+        None => Vec::new(),
+        _ => return Err(invalid("invalid key-value pairs in binary record")),
+    };
+
+    Ok(Record {
+        time: DateTime::from_timestamp_nanos(ts),
+        level: level.ok_or_else(|| invalid("invalid level in binary record"))?,
+        msg: string(take("msg"))?,
+        tag: string(take("tag"))?,
+        module: string(take("module"))?,
+        file: string(take("file"))?,
+        line,
+        kv,
+    })
+}
+
+fn from_msgpack(value: msgpack::Value) -> io::Result<Value> {
+    use msgpack::Value as M;
+
+    let value = match value {
+        M::Nil => Value::Null,
+        M::Bool(v) => Value::Bool(v),
+        M::Int(v) => Value::Int(v),
+        M::Uint(v) => Value::Uint(v),
+        M::F32(v) => Value::Float(f64::from(v)),
+        M::F64(v) => Value::Float(v),
+        M::Str(v) => Value::Str(v),
+        M::Bin(v) => Value::Bytes(v),
+        M::Array(v) => Value::Array(v.into_iter().map(from_msgpack).collect::<io::Result<_>>()?),
+        M::Map(v) => Value::Map(
+            v.into_iter()
+                .map(|(k, v)| match k {
+                    M::Str(k) => Ok((k, from_msgpack(v)?)),
+                    _ => Err(invalid("non-string key in binary record")),
+                })
+                .collect::<io::Result<_>>()?,
+        ),
+        M::Ext(..) => return Err(invalid("unexpected extension type in binary record")),
+    };
+
+    Ok(value)
+}
+
+fn from_cbor(value: cbor::Value) -> io::Result<Value> {
+    use cbor::Value as C;
+
+    let value = match value {
+        C::Null => Value::Null,
+        C::Bool(v) => Value::Bool(v),
+        C::Int(v) => Value::Int(v),
+        C::Uint(v) => Value::Uint(v),
+        C::Float(v) => Value::Float(v),
+        C::Str(v) => Value::Str(v),
+        C::Bytes(v) => Value::Bytes(v),
+        C::Array(v) => Value::Array(v.into_iter().map(from_cbor).collect::<io::Result<_>>()?),
+        C::Map(v) => Value::Map(
+            v.into_iter()
+                .map(|(k, v)| match k {
+                    C::Str(k) => Ok((k, from_cbor(v)?)),
+                    _ => Err(invalid("non-string key in binary record")),
+                })
+                .collect::<io::Result<_>>()?,
+        ),
+    };
+
+    Ok(value)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated binary record")
+}
+
+
+/// Encodes the specified record including its length prefix.
+pub(crate) fn encode(
+    encoding: Encoding,
+    time: DateTime<Utc>,
+    record: &slog::Record,
+    values: &OwnedKVList,
+) -> Vec<u8> {
+    let mut collector = Collector(Vec::new());
+
+    // collecting into a vector never fails
+    let _ = record.kv().serialize(record, &mut collector);
+    let _ = values.serialize(record, &mut collector);
+
+    let level = Level::from(record.level()).to_str_lowercase();
+    let msg = record.msg().to_string();
+    let ts = time.timestamp() * 1_000_000_000 + i64::from(time.timestamp_subsec_nanos());
+
+    let mut entries = vec![
+        ("ts", Value::Int(ts)),
+        ("level", Value::Str(level.into())),
+        ("msg", Value::Str(msg)),
+    ];
+
+    if !record.tag().is_empty() {
+        entries.push(("tag", Value::Str(record.tag().into())));
+    }
+
+    entries.push(("module", Value::Str(record.module().into())));
+    entries.push(("file", Value::Str(record.file().into())));
+    entries.push(("line", Value::Uint(u64::from(record.line()))));
+    entries.push(("kv", Value::Map(collector.0)));
+
+    let mut buf = vec![0; 4];
+
+    match encoding {
+        Encoding::MessagePack => {
+            msgpack::write_map_len(&mut buf, entries.len());
+            for (key, value) in &entries {
+                msgpack::write_str(&mut buf, key);
+                write_msgpack(&mut buf, value);
+            }
+        },
+        Encoding::Cbor => {
+            cbor::write_map_len(&mut buf, entries.len());
+            for (key, value) in &entries {
+                cbor::write_str(&mut buf, key);
+                write_cbor(&mut buf, value);
+            }
+        },
+    }
+
+    let len = (buf.len() - 4) as u32;
+    buf[..4].copy_from_slice(&len.to_be_bytes());
+    buf
+}
+
+fn write_msgpack(buf: &mut Vec<u8>, value: &Value) {
+    match *value {
+        Value::Null => msgpack::write_nil(buf),
+        Value::Bool(v) => msgpack::write_bool(buf, v),
+        Value::Int(v) => msgpack::write_int(buf, v),
+        Value::Uint(v) => msgpack::write_uint(buf, v),
+        Value::Float(v) => msgpack::write_f64(buf, v),
+        Value::Str(ref v) => msgpack::write_str(buf, v),
+        Value::Bytes(ref v) => msgpack::write_bin(buf, v),
+        Value::Array(ref v) => {
+            msgpack::write_array_len(buf, v.len());
+            for value in v {
+                write_msgpack(buf, value);
+            }
+        },
+        Value::Map(ref v) => {
+            msgpack::write_map_len(buf, v.len());
+            for (key, value) in v {
+                msgpack::write_str(buf, key);
+                write_msgpack(buf, value);
+            }
+        },
+    }
+}
+
+fn write_cbor(buf: &mut Vec<u8>, value: &Value) {
+    match *value {
+        Value::Null => cbor::write_null(buf),
+        Value::Bool(v) => cbor::write_bool(buf, v),
+        Value::Int(v) => cbor::write_int(buf, v),
+        Value::Uint(v) => cbor::write_uint(buf, v),
+        Value::Float(v) => cbor::write_f64(buf, v),
+        Value::Str(ref v) => cbor::write_str(buf, v),
+        Value::Bytes(ref v) => cbor::write_bytes(buf, v),
+        Value::Array(ref v) => {
+            cbor::write_array_len(buf, v.len());
+            for value in v {
+                write_cbor(buf, value);
+            }
+        },
+        Value::Map(ref v) => {
+            cbor::write_map_len(buf, v.len());
+            for (key, value) in v {
+                cbor::write_str(buf, key);
+                write_cbor(buf, value);
+            }
+        },
+    }
+}
+
+
+/// Collects key-value pairs with their types preserved.
+struct Collector(Vec<(String, Value)>);
+
+impl Collector {
+    fn push(&mut self, key: Key, value: Value) -> slog::Result {
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+impl slog::Serializer for Collector {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.push(key, Value::Str(val.to_string()))
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> slog::Result {
+        self.push(key, Value::Uint(val as u64))
+    }
+
+    fn emit_isize(&mut self, key: Key, val: isize) -> slog::Result {
+        self.push(key, Value::Int(val as i64))
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.push(key, Value::Bool(val))
+    }
+
+    fn emit_char(&mut self, key: Key, val: char) -> slog::Result {
+        self.push(key, Value::Str(val.to_string()))
+    }
+
+    fn emit_u8(&mut self, key: Key, val: u8) -> slog::Result {
+        self.push(key, Value::Uint(u64::from(val)))
+    }
+
+    fn emit_i8(&mut self, key: Key, val: i8) -> slog::Result {
+        self.push(key, Value::Int(i64::from(val)))
+    }
+
+    fn emit_u16(&mut self, key: Key, val: u16) -> slog::Result {
+        self.push(key, Value::Uint(u64::from(val)))
+    }
+
+    fn emit_i16(&mut self, key: Key, val: i16) -> slog::Result {
+        self.push(key, Value::Int(i64::from(val)))
+    }
+
+    fn emit_u32(&mut self, key: Key, val: u32) -> slog::Result {
+        self.push(key, Value::Uint(u64::from(val)))
+    }
+
+    fn emit_i32(&mut self, key: Key, val: i32) -> slog::Result {
+        self.push(key, Value::Int(i64::from(val)))
+    }
+
+    fn emit_f32(&mut self, key: Key, val: f32) -> slog::Result {
+        self.push(key, Value::Float(f64::from(val)))
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.push(key, Value::Uint(val))
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.push(key, Value::Int(val))
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.push(key, Value::Float(val))
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        self.push(key, Value::Str(val.into()))
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        self.push(key, Value::Null)
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.push(key, Value::Null)
+    }
+
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> slog::Result {
+        let value = serde_json::to_value(value.as_serde())
+            .map(Value::from_json)
+            .unwrap_or_else(|e| Value::Str(e.to_string()));
+
+        self.push(key, value)
+    }
+}
@@ -0,0 +1,233 @@
+//! Minimal CBOR (RFC 7049) encoding and decoding.
+
+use std::io::{self, Read};
+
+
+/// The maximum nesting depth of arrays, maps and tags accepted when reading.
+const MAX_DEPTH: usize = 128;
+
+/// Appends the head of a data item with the specified major type and
+/// argument in its shortest representation.
+fn write_head(buf: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+
+    if value < 24 {
+        buf.push(major | value as u8);
+    } else if value <= 0xff {
+        buf.push(major | 24);
+        buf.push(value as u8);
+    } else if value <= 0xffff {
+        buf.push(major | 25);
+        buf.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= 0xffff_ffff {
+        buf.push(major | 26);
+        buf.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Appends an unsigned integer.
+pub fn write_uint(buf: &mut Vec<u8>, value: u64) {
+    write_head(buf, 0, value);
+}
+
+/// Appends a signed integer.
+pub fn write_int(buf: &mut Vec<u8>, value: i64) {
+    if value >= 0 {
+        write_head(buf, 0, value as u64);
+    } else {
+        write_head(buf, 1, !(value as u64));
+    }
+}
+
+/// Appends a byte string.
+pub fn write_bytes(buf: &mut Vec<u8>, value: &[u8]) {
+    write_head(buf, 2, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+/// Appends a text string.
+pub fn write_str(buf: &mut Vec<u8>, value: &str) {
+    write_head(buf, 3, value.len() as u64);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Appends the head of an array with the specified number of elements.
+pub fn write_array_len(buf: &mut Vec<u8>, len: usize) {
+    write_head(buf, 4, len as u64);
+}
+
+/// Appends the head of a map with the specified number of entries.
+pub fn write_map_len(buf: &mut Vec<u8>, len: usize) {
+    write_head(buf, 5, len as u64);
+}
+
+/// Appends a boolean.
+pub fn write_bool(buf: &mut Vec<u8>, value: bool) {
+    buf.push(if value { 0xf5 } else { 0xf4 });
+}
+
+/// Appends `null`.
+pub fn write_null(buf: &mut Vec<u8>) {
+    buf.push(0xf6);
+}
+
+/// Appends a double-precision float.
+pub fn write_f64(buf: &mut Vec<u8>, value: f64) {
+    buf.push(0xfb);
+    buf.extend_from_slice(&value.to_bits().to_be_bytes());
+}
+
+
+/// A decoded CBOR data item.
+///
+/// Tags are skipped, i.e. only the tagged item is returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Bytes(Vec<u8>),
+    Str(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+/// Reads a single data item.
+///
+/// Indefinite-length items are not supported. Items nested deeper than 128
+/// arrays, maps or tags are rejected as invalid data.
+pub fn read<R: Read>(r: &mut R) -> io::Result<Value> {
+    read_1(r, MAX_DEPTH)
+}
+
+fn read_1<R: Read>(r: &mut R, depth: usize) -> io::Result<Value> {
+    let initial = read_u8(r)?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+
+    if major == 7 {
+        return match info {
+            20 => Ok(Value::Bool(false)),
+            21 => Ok(Value::Bool(true)),
+            22 | 23 => Ok(Value::Null),
+            25 => Ok(Value::Float(f16_to_f64(read_u16(r)?))),
+            26 => Ok(Value::Float(f64::from(f32::from_bits(read_u32(r)?)))),
+            27 => Ok(Value::Float(f64::from_bits(read_u64(r)?))),
+            _ => Err(invalid("unsupported cbor simple value")),
+        };
+    }
+
+    let arg = match info {
+        0..=23 => u64::from(info),
+        24 => u64::from(read_u8(r)?),
+        25 => u64::from(read_u16(r)?),
+        26 => u64::from(read_u32(r)?),
+        27 => read_u64(r)?,
+        31 => return Err(invalid("indefinite-length cbor items are not supported")),
+        _ => return Err(invalid("invalid cbor item")),
+    };
+
+    let depth = match major {
+        4..=6 => nested(depth)?,
+        _ => depth,
+    };
+
+    let value = match major {
+        0 => Value::Uint(arg),
+        1 => {
+            if arg > i64::MAX as u64 {
+                return Err(invalid("cbor integer out of range"));
+            }
+            Value::Int(-1 - arg as i64)
+        },
+        2 => Value::Bytes(read_bytes(r, arg)?),
+        3 => String::from_utf8(read_bytes(r, arg)?)
+            .map(Value::Str)
+            .map_err(|_| invalid("invalid utf-8 in cbor string"))?,
+        4 => {
+            let mut values = Vec::with_capacity((arg as usize).min(1024));
+            for _ in 0..arg {
+                values.push(read_1(r, depth)?);
+            }
+            Value::Array(values)
+        },
+        5 => {
+            let mut entries = Vec::with_capacity((arg as usize).min(1024));
+            for _ in 0..arg {
+                let key = read_1(r, depth)?;
+                let value = read_1(r, depth)?;
+                entries.push((key, value));
+            }
+            Value::Map(entries)
+        },
+        _ => read_1(r, depth)?,
+    };
+
+    Ok(value)
+}
+
+fn nested(depth: usize) -> io::Result<usize> {
+    depth.checked_sub(1).ok_or_else(|| invalid("cbor item nested too deeply"))
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = f64::from(half & 0x3ff);
+
+    let value = match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mant + 1024.0) * 2f64.powi(i32::from(exp) - 25),
+    };
+
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+fn read_bytes<R: Read>(r: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+
+    if buf.len() as u64 == len {
+        Ok(buf)
+    } else {
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated cbor item"))
+    }
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -1,12 +1,15 @@
 //! Common configuration types.
 
+use std;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 
-use slog;
+use slog::{self, Drain};
+use slog_async::{Async, AsyncBuilder, OverflowStrategy};
 
 
 /// The modes in which a log file can be opened.
@@ -73,13 +76,82 @@ pub enum Target {
     /// A file.
     File {
         /// The path at which the file is located.
+        ///
+        /// If the file is rotated, this path is never written. Each file,
+        /// including the current one, is named by extending this path with a
+        /// suffix (see [`Rotation`](Rotation)).
         path: PathBuf,
 
         /// The mode with which the file will be opened.
         mode: OpenMode,
+
+        /// The rotation settings, if the file should be rotated.
+        rotate: Option<Rotation>,
+
+        /// The retention policy for rotated files.
+        retention: Option<Retention>,
+
+        /// The conditions under which the file should be reopened.
+        reopen: Option<Reopen>,
+
+        /// The compression of the written stream, if the file should be
+        /// compressed while writing (see [`Compressed`](::output::Compressed)).
+        compression: Option<Compression>,
+    },
+
+    /// A network connection to which newline-delimited records are written.
+    Network {
+        /// The endpoint to connect to.
+        connect: Endpoint,
+
+        /// The maximum number of records buffered while disconnected.
+        buffer: usize,
+
+        /// The maximum delay between two connection attempts.
+        backoff: Duration,
+    },
+
+    /// A preallocated file of fixed size in which new records overwrite the
+    /// oldest ones (see [`RingFile`](::output::RingFile)).
+    Ring {
+        /// The path at which the file is located.
+        path: PathBuf,
+
+        /// The total size of the file, including its header.
+        size: Size,
     },
 }
 
+impl Target {
+    /// Returns a network target with default settings for the specified
+    /// endpoint.
+    pub fn network(connect: Endpoint) -> Self {
+        Target::Network {
+            connect,
+            buffer: 1000,
+            backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Returns `true` if the writer for this target acts at record
+    /// boundaries, i.e. if it has to be flushed after each record.
+    ///
+    /// This is the case for files that are rotated, reopened, or compressed,
+    /// and for ring files.
+    pub fn is_record_oriented(&self) -> bool {
+        match *self {
+            Target::File {
+                ref rotate,
+                ref reopen,
+                compression,
+                ..
+            } => rotate.is_some() || reopen.is_some() || compression.is_some(),
+            Target::Ring { .. } => true,
+            _ => false,
+        }
+    }
+}
+
 impl Default for Target {
     fn default() -> Self {
         Target::Stdout
@@ -96,10 +168,49 @@ impl Serialize for Target {
         match *self {
             Target::Stdout => serializer.serialize_str("stdout"),
             Target::Stderr => serializer.serialize_str("stderr"),
-            Target::File { ref path, ref mode } => {
-                let mut state = serializer.serialize_struct("File", 1)?;
+            Target::File {
+                ref path,
+                ref mode,
+                ref rotate,
+                ref retention,
+                ref reopen,
+                ref compression,
+            } => {
+                let len = 2 + rotate.iter().count() + retention.iter().count()
+                    + reopen.iter().count() + compression.iter().count();
+
+                let mut state = serializer.serialize_struct("File", len)?;
                 state.serialize_field("path", path)?;
                 state.serialize_field("mode", mode)?;
+                if let Some(ref rotate) = *rotate {
+                    state.serialize_field("rotate", rotate)?;
+                }
+                if let Some(ref retention) = *retention {
+                    state.serialize_field("retention", retention)?;
+                }
+                if let Some(ref reopen) = *reopen {
+                    state.serialize_field("reopen", reopen)?;
+                }
+                if let Some(ref compression) = *compression {
+                    state.serialize_field("compression", compression)?;
+                }
+                state.end()
+            },
+            Target::Network {
+                ref connect,
+                buffer,
+                backoff,
+            } => {
+                let mut state = serializer.serialize_struct("Network", 3)?;
+                state.serialize_field("connect", connect)?;
+                state.serialize_field("buffer", &buffer)?;
+                state.serialize_field("backoff", &backoff)?;
+                state.end()
+            },
+            Target::Ring { ref path, size } => {
+                let mut state = serializer.serialize_struct("Ring", 2)?;
+                state.serialize_field("ring", path)?;
+                state.serialize_field("size", &size)?;
                 state.end()
             },
         }
@@ -114,6 +225,15 @@ impl<'de> Deserialize<'de> for Target {
         enum Field {
             Path,
             Mode,
+            Rotate,
+            Retention,
+            Reopen,
+            Compression,
+            Connect,
+            Buffer,
+            Backoff,
+            Ring,
+            Size,
             _Ignore,
         }
 
@@ -128,7 +248,10 @@ impl<'de> Deserialize<'de> for Target {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                        formatter.write_str("`path`")
+                        formatter.write_str(
+                            "`path`, `mode`, `rotate`, `retention`, `reopen`, \
+                             `compression`, `connect`, `buffer`, `backoff`, `ring` or `size`",
+                        )
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -138,6 +261,15 @@ impl<'de> Deserialize<'de> for Target {
                         match value {
                             "path" => Ok(Field::Path),
                             "mode" => Ok(Field::Mode),
+                            "rotate" => Ok(Field::Rotate),
+                            "retention" => Ok(Field::Retention),
+                            "reopen" => Ok(Field::Reopen),
+                            "compression" => Ok(Field::Compression),
+                            "connect" => Ok(Field::Connect),
+                            "buffer" => Ok(Field::Buffer),
+                            "backoff" => Ok(Field::Backoff),
+                            "ring" => Ok(Field::Ring),
+                            "size" => Ok(Field::Size),
                             _ => Ok(Field::_Ignore),
                         }
                     }
@@ -167,6 +299,9 @@ impl<'de> Deserialize<'de> for Target {
                 match s {
                     "stdout" => Ok(Target::Stdout),
                     "stderr" => Ok(Target::Stderr),
+                    s if s.contains("://") => {
+                        s.parse().map(Target::network).map_err(serde::de::Error::custom)
+                    },
                     s => Err(serde::de::Error::unknown_variant(s, &["stdout", "stderr"])),
                 }
             }
@@ -177,6 +312,15 @@ impl<'de> Deserialize<'de> for Target {
             {
                 let mut path = None;
                 let mut mode = None;
+                let mut rotate = None;
+                let mut retention = None;
+                let mut reopen = None;
+                let mut compression = None;
+                let mut connect = None;
+                let mut buffer = None;
+                let mut backoff = None;
+                let mut ring = None;
+                let mut size = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -192,15 +336,121 @@ impl<'de> Deserialize<'de> for Target {
                             }
                             mode = Some(map.next_value()?);
                         },
+                        Field::Rotate => {
+                            if rotate.is_some() {
+                                return Err(serde::de::Error::duplicate_field("rotate"));
+                            }
+                            rotate = Some(map.next_value()?);
+                        },
+                        Field::Retention => {
+                            if retention.is_some() {
+                                return Err(serde::de::Error::duplicate_field("retention"));
+                            }
+                            retention = Some(map.next_value()?);
+                        },
+                        Field::Reopen => {
+                            if reopen.is_some() {
+                                return Err(serde::de::Error::duplicate_field("reopen"));
+                            }
+                            reopen = Some(map.next_value()?);
+                        },
+                        Field::Compression => {
+                            if compression.is_some() {
+                                return Err(serde::de::Error::duplicate_field("compression"));
+                            }
+                            compression = Some(map.next_value()?);
+                        },
+                        Field::Connect => {
+                            if connect.is_some() {
+                                return Err(serde::de::Error::duplicate_field("connect"));
+                            }
+                            connect = Some(map.next_value()?);
+                        },
+                        Field::Buffer => {
+                            if buffer.is_some() {
+                                return Err(serde::de::Error::duplicate_field("buffer"));
+                            }
+                            buffer = Some(map.next_value()?);
+                        },
+                        Field::Backoff => {
+                            if backoff.is_some() {
+                                return Err(serde::de::Error::duplicate_field("backoff"));
+                            }
+                            backoff = Some(map.next_value()?);
+                        },
+                        Field::Ring => {
+                            if ring.is_some() {
+                                return Err(serde::de::Error::duplicate_field("ring"));
+                            }
+                            ring = Some(map.next_value()?);
+                        },
+                        Field::Size => {
+                            if size.is_some() {
+                                return Err(serde::de::Error::duplicate_field("size"));
+                            }
+                            size = Some(map.next_value()?);
+                        },
                         _ => {
                             let _ignore: serde::de::IgnoredAny = map.next_value()?;
                         },
                     }
                 }
 
+                // the options of each variant, and whether they have been set
+                let file = [
+                    ("path", path.is_some()),
+                    ("mode", mode.is_some()),
+                    ("rotate", rotate.is_some()),
+                    ("retention", retention.is_some()),
+                    ("reopen", reopen.is_some()),
+                    ("compression", compression.is_some()),
+                ];
+                let network = [
+                    ("connect", connect.is_some()),
+                    ("buffer", buffer.is_some()),
+                    ("backoff", backoff.is_some()),
+                ];
+                let ringed = [("ring", ring.is_some()), ("size", size.is_some())];
+
+                if let Some(ring) = ring {
+                    reject("ring", &file)?;
+                    reject("ring", &network)?;
+
+                    let size = size.unwrap_or(Size(1024 * 1024));
+                    return Ok(Target::Ring { path: ring, size });
+                }
+
+                if let Some(connect) = connect {
+                    reject("connect", &file)?;
+                    reject("connect", &ringed)?;
+
+                    let mut target = Target::network(connect);
+                    if let Target::Network {
+                        buffer: ref mut b,
+                        backoff: ref mut d,
+                        ..
+                    } = target
+                    {
+                        *b = buffer.unwrap_or(*b);
+                        *d = backoff.unwrap_or(*d);
+                    }
+
+                    return Ok(target);
+                }
+
                 let path = path.ok_or_else(|| serde::de::Error::missing_field("path"))?;
+                reject("path", &network)?;
+                reject("path", &ringed)?;
+
                 let mode = mode.unwrap_or_default();
-                Ok(Target::File { path, mode })
+                Ok(Target::File {
+                    path,
+                    mode,
+                    rotate,
+                    retention,
+                    reopen,
+                    compression,
+                })
             }
 
             fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
@@ -214,7 +464,504 @@ impl<'de> Deserialize<'de> for Target {
                     .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
 
                 let mode = mode.unwrap_or_default();
-                Ok(Target::File { path, mode })
+                Ok(Target::File {
+                    path,
+                    mode,
+                    rotate: None,
+                    retention: None,
+                    reopen: None,
+                    compression: None,
+                })
+            }
+        }
+
+        /// Returns an error naming the first of the specified options that has
+        /// been set, as it does not apply to the target selected by `key`.
+        fn reject<E: serde::de::Error>(key: &str, options: &[(&str, bool)]) -> Result<(), E> {
+            match options.iter().find(|&&(_, set)| set) {
+                Some(&(option, _)) => Err(E::custom(format_args!(
+                    "`{}` cannot be combined with `{}`",
+                    option, key
+                ))),
+                None => Ok(()),
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+
+/// Rotation settings for a log file.
+///
+/// **Note:** A rotated log file is never written directly to its configured
+/// path, not even the current file. Instead, the path is extended by a
+/// [`Suffix`](Suffix) identifying the current file, e.g. `app.log.3` or
+/// `app.log.2018-07-01`. Use `symlink` to provide a fixed path to the current
+/// file.
+///
+/// A new file is started whenever one of the configured limits is exceeded.
+/// Rotation happens only between records, thus a file may exceed the
+/// configured size by at most one record.
+///
+/// If no limit is specified, a single file named according to the suffix is
+/// used.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Rotation {
+    /// The maximum size of a single file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Size>,
+
+    /// The interval after which a new file should be started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Interval>,
+
+    /// The suffix used to name the individual files.
+    #[serde(default)]
+    pub suffix: Suffix,
+
+    /// The path of a symbolic link that should always point to the current
+    /// file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink: Option<PathBuf>,
+}
+
+
+/// Calendar-interval after which a log file should be rotated.
+///
+/// Interval boundaries are always computed in UTC.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    /// Start a new file every full hour.
+    Hourly,
+
+    /// Start a new file every day at midnight.
+    Daily,
+}
+
+impl Interval {
+    /// Returns the length of this interval in seconds.
+    pub fn seconds(self) -> i64 {
+        match self {
+            Interval::Hourly => 60 * 60,
+            Interval::Daily => 24 * 60 * 60,
+        }
+    }
+}
+
+
+/// The suffix used to name rotated log files.
+///
+/// Defaults to [`Index`](Suffix::Index).
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Suffix {
+    /// An increasing index, e.g. `app.log.1`, `app.log.2`, ...
+    Index,
+
+    /// The date (and hour, if rotated hourly) of the interval in which the file
+    /// has been created, e.g. `app.log.2018-07-01`. If multiple files are
+    /// created in the same interval, an additional index will be appended,
+    /// e.g. `app.log.2018-07-01.1`.
+    Date,
+}
+
+impl Default for Suffix {
+    fn default() -> Self {
+        Suffix::Index
+    }
+}
+
+
+/// Retention policy for rotated log files.
+///
+/// The policy is applied by a background thread each time a file has been
+/// rotated, as well as once when the file target is opened. Rotated files are
+/// optionally compressed first, then all specified limits are enforced by
+/// removing the oldest rotated files. The file currently being written to is
+/// never removed, but is accounted for in the total size.
+///
+/// A retention policy can only be applied to rotated files.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Retention {
+    /// The maximum number of rotated files to keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+
+    /// The maximum age of rotated files, based on their last modification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age: Option<Duration>,
+
+    /// The maximum total size of all files, including the current one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Size>,
+
+    /// The compression to apply to rotated files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress: Option<Compression>,
+}
+
+
+/// Conditions under which a log file should be reopened.
+///
+/// Reopening allows external tools like `logrotate` to move log files away
+/// while the logger is running. The file is reopened (and created, if
+/// necessary) in `append`-mode in-between records, thus no records are lost.
+/// For rotated files, the current file will be reopened.
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct Reopen {
+    /// Reopen the file each time the process receives `SIGHUP`.
+    ///
+    /// Only supported on Unix and if the `signal` feature is enabled.
+    #[serde(default)]
+    pub signal: bool,
+
+    /// Reopen the file if it has been moved or removed.
+    ///
+    /// This is checked at most once per second.
+    #[serde(default)]
+    pub moved: bool,
+}
+
+
+/// A network endpoint, specified as `tcp://host:port` or `unix:///path`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Endpoint {
+    /// A TCP endpoint specified as `host:port`.
+    Tcp(String),
+
+    /// A unix stream socket at the specified path.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Endpoint::Tcp(ref addr) => write!(f, "tcp://{}", addr),
+            Endpoint::Unix(ref path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            if !addr.is_empty() {
+                return Ok(Endpoint::Tcp(addr.to_owned()));
+            }
+        } else if let Some(path) = s.strip_prefix("unix://") {
+            if !path.is_empty() {
+                return Ok(Endpoint::Unix(path.into()));
+            }
+        }
+
+        Err(format!("invalid endpoint `{}`, expected tcp://host:port or unix:///path", s))
+    }
+}
+
+impl Serialize for Endpoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Endpoint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+
+/// Compression algorithm for log files.
+///
+/// Each variant requires the feature of the same name.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Gzip compression.
+    #[cfg(feature = "gzip")]
+    Gzip,
+
+    /// Zstandard compression.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the file extension (without leading dot) commonly used for files
+    /// compressed with this algorithm.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gz",
+
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zst",
+        }
+    }
+}
+
+
+/// A size in bytes.
+///
+/// Can be deserialized from an integer representing the number of bytes or a
+/// string with an optional unit, e.g. `"512"`, `"10kB"`, `"10KiB"`, `"1.5 GiB"`.
+/// Decimal units (`kB`, `MB`, `GB`, `TB`) use multiples of 1000, binary units
+/// (`KiB`, `MiB`, `GiB`, `TiB`) multiples of 1024. Units are case-insensitive.
+///
+/// Always serialized as number of bytes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Size(pub u64);
+
+impl Size {
+    /// Returns the number of bytes.
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+
+        let (value, unit) = s.split_at(split);
+        let value: f64 = value.parse().map_err(|_| format!("invalid size `{}`", s))?;
+
+        let factor: u64 = match unit.trim().to_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" => 1000,
+            "m" | "mb" => 1000 * 1000,
+            "g" | "gb" => 1000 * 1000 * 1000,
+            "t" | "tb" => 1000 * 1000 * 1000 * 1000,
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            "tib" => 1 << 40,
+            unit => return Err(format!("invalid size unit `{}`", unit)),
+        };
+
+        Ok(Size((value * factor as f64) as u64))
+    }
+}
+
+impl Serialize for Size {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Size;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number of bytes or a size like \"10MiB\"")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Size, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Size(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Size, E>
+            where
+                E: serde::de::Error,
+            {
+                if value >= 0 {
+                    Ok(Size(value as u64))
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+                }
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Size, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+
+/// A span of time.
+///
+/// Can be deserialized from an integer representing the number of seconds or a
+/// string of one or more numbers with units, e.g. `"500ms"`, `"30s"`,
+/// `"1h30m"` or `"7d"`. Supported units are `ms`, `s`, `m`, `h`, `d` and `w`.
+///
+/// Always serialized as string.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Duration(pub std::time::Duration);
+
+impl Duration {
+    /// Creates a new duration spanning the specified number of seconds.
+    pub fn from_secs(secs: u64) -> Self {
+        Duration(std::time::Duration::from_secs(secs))
+    }
+
+    /// Creates a new duration spanning the specified number of milliseconds.
+    pub fn from_millis(millis: u64) -> Self {
+        Duration(std::time::Duration::from_millis(millis))
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Duration(duration)
+    }
+}
+
+impl From<Duration> for std::time::Duration {
+    fn from(duration: Duration) -> Self {
+        duration.0
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: &[(&str, u64)] = &[
+            ("w", 7 * 24 * 60 * 60 * 1000),
+            ("d", 24 * 60 * 60 * 1000),
+            ("h", 60 * 60 * 1000),
+            ("m", 60 * 1000),
+            ("s", 1000),
+            ("ms", 1),
+        ];
+
+        let mut millis = self.0.as_secs() * 1000 + u64::from(self.0.subsec_millis());
+        if millis == 0 {
+            return f.write_str("0s");
+        }
+
+        for &(unit, factor) in UNITS {
+            if millis >= factor {
+                write!(f, "{}{}", millis / factor, unit)?;
+                millis %= factor;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Duration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid duration `{}`", s);
+
+        let mut rest = s.trim();
+        let mut millis: u64 = 0;
+
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        while !rest.is_empty() {
+            let split = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let (value, tail) = rest.split_at(split);
+            let value: u64 = value.parse().map_err(|_| invalid())?;
+
+            let split = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+            let (unit, tail) = tail.split_at(split);
+
+            let factor = match unit.trim() {
+                "ms" => 1,
+                "" | "s" => 1000,
+                "m" => 60 * 1000,
+                "h" => 60 * 60 * 1000,
+                "d" => 24 * 60 * 60 * 1000,
+                "w" => 7 * 24 * 60 * 60 * 1000,
+                unit => return Err(format!("invalid duration unit `{}`", unit)),
+            };
+
+            millis = value
+                .checked_mul(factor)
+                .and_then(|value| millis.checked_add(value))
+                .ok_or_else(invalid)?;
+            rest = tail.trim();
+        }
+
+        Ok(Duration::from_millis(millis))
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Duration;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number of seconds or a duration like \"1h30m\"")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Duration, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Duration::from_secs(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Duration, E>
+            where
+                E: serde::de::Error,
+            {
+                if value >= 0 {
+                    Ok(Duration::from_secs(value as u64))
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+                }
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Duration, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
             }
         }
 
@@ -267,7 +1014,27 @@ impl Into<slog::Level> for Level {
 }
 
 impl Level {
-    fn to_str_lowercase(self) -> &'static str {
+    /// Returns the syslog severity corresponding to this level.
+    ///
+    /// Both `Debug` and `Trace` map to the `debug` severity (7), `Info` maps to
+    /// `informational` (6), all other levels map to their syslog namesakes.
+    pub fn severity(self) -> u8 {
+        match self {
+            Level::Critical => 2,
+            Level::Error => 3,
+            Level::Warning => 4,
+            Level::Info => 6,
+            Level::Debug => 7,
+            Level::Trace => 7,
+        }
+    }
+
+    /// Parses a level from its full or abbreviated name, ignoring case.
+    fn parse(name: &str) -> Option<Level> {
+        slog::Level::from_str(name).ok().map(Level::from)
+    }
+
+    pub(crate) fn to_str_lowercase(self) -> &'static str {
         match self {
             Level::Critical => "critical",
             Level::Error => "error",
@@ -338,6 +1105,187 @@ impl<'de> Deserialize<'de> for Level {
 }
 
 
+/// Logging levels for filtering, optionally overridden per module.
+///
+/// Each directive maps a module path to the minimal level of records emitted
+/// from this module or any of its submodules. If multiple directives match a
+/// record, the one with the longest module path takes precedence. Records not
+/// matching any directive are filtered with the default level.
+///
+/// Directives can be specified as a single level (e.g. `"info"`), as a string
+/// in `RUST_LOG` syntax (e.g. `"info,hyper=warn,myapp::db=trace"`), or as a
+/// map from module paths to levels, with the special key `default` for the
+/// default level. A module given without level in `RUST_LOG` syntax enables all
+/// levels for this module.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Directives {
+    /// The default level, applied to all records not matching any module.
+    pub default: Level,
+
+    /// The levels for specific modules, mapped by their module paths.
+    pub modules: BTreeMap<String, Level>,
+}
+
+impl Directives {
+    /// Returns the level applying to records emitted from the specified module.
+    pub fn level(&self, module: &str) -> Level {
+        self.modules
+            .iter()
+            .filter(|&(path, _)| is_submodule(module, path))
+            .max_by_key(|&(path, _)| path.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+}
+
+impl From<Level> for Directives {
+    fn from(level: Level) -> Self {
+        Directives {
+            default: level,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for Directives {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.default.to_str_lowercase())?;
+
+        for (module, level) in &self.modules {
+            write!(f, ",{}={}", module, level.to_str_lowercase())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Directives {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut directives = Directives::default();
+
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+
+            match parts.next() {
+                Some(level) => {
+                    let level = Level::parse(level.trim())
+                        .ok_or_else(|| format!("invalid level in directive `{}`", directive))?;
+
+                    if name.is_empty() {
+                        return Err(format!("missing module in directive `{}`", directive));
+                    }
+
+                    directives.modules.insert(name.to_owned(), level);
+                },
+                None => match Level::parse(name) {
+                    Some(level) => directives.default = level,
+                    None => {
+                        directives.modules.insert(name.to_owned(), Level::Trace);
+                    },
+                },
+            }
+        }
+
+        Ok(directives)
+    }
+}
+
+impl Serialize for Directives {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        if self.modules.is_empty() {
+            return self.default.serialize(serializer);
+        }
+
+        let mut map = serializer.serialize_map(Some(self.modules.len() + 1))?;
+        map.serialize_entry("default", &self.default)?;
+
+        for (module, level) in &self.modules {
+            map.serialize_entry(module, level)?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Directives {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Directives;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a level, a directive string, or a map of levels")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Directives, E>
+            where
+                E: serde::de::Error,
+            {
+                slog::Level::from_usize(value as usize)
+                    .map(|level| Level::from(level).into())
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Directives, E>
+            where
+                E: serde::de::Error,
+            {
+                if value >= 0 {
+                    self.visit_u64(value as u64)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+                }
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Directives, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Directives, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut directives = Directives::default();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    let level = map.next_value()?;
+
+                    if key == "default" {
+                        directives.default = level;
+                    } else {
+                        directives.modules.insert(key, level);
+                    }
+                }
+
+                Ok(directives)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Returns `true` if `module` is equal to `parent` or one of its submodules.
+pub(crate) fn is_submodule(module: &str, parent: &str) -> bool {
+    module.starts_with(parent)
+        && (module.len() == parent.len() || module[parent.len()..].starts_with("::"))
+}
+
+
 /// Timestamp format and timezone.
 ///
 /// Defaults to [`Rfc3339Utc`](Timestamp::Rfc3339Utc).
@@ -359,6 +1307,110 @@ impl Default for Timestamp {
 }
 
 
+/// Whether lines should be prefixed with their `sd-daemon` priority (e.g.
+/// `<4>`), allowing systemd-journald to assign the severities of the records.
+///
+/// Defaults to [`Auto`](Journal::Auto).
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Journal {
+    /// Prefix lines if the target is the standard output or error stream and
+    /// this stream is connected to the journal, as indicated by the
+    /// `JOURNAL_STREAM` environment variable.
+    Auto,
+
+    /// Always prefix lines.
+    Always,
+
+    /// Never prefix lines.
+    Never,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal::Auto
+    }
+}
+
+
+/// Settings for the worker thread of an `Async` drain, configured as `async`
+/// section.
+///
+/// Only applies to drains built via the default `Async` factories. Nested
+/// configurations (e.g. of a `multi`-type logger) are built as synchronous
+/// drains, thus their settings are ignored.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct AsyncWorker {
+    /// The number of records the channel to the worker thread can hold.
+    ///
+    /// Defaults to the default of `slog_async`.
+    #[serde(default, rename = "channel-size", skip_serializing_if = "Option::is_none")]
+    pub channel_size: Option<usize>,
+
+    /// What to do with records if the channel is full.
+    #[serde(default)]
+    pub overflow: Overflow,
+
+    /// The name of the worker thread.
+    #[serde(default, rename = "thread-name", skip_serializing_if = "Option::is_none")]
+    pub thread_name: Option<String>,
+}
+
+impl AsyncWorker {
+    /// Returns a builder for an `Async` drain wrapping the specified drain,
+    /// configured according to these settings.
+    pub fn builder<D>(&self, drain: D) -> AsyncBuilder<D>
+    where
+        D: Drain<Err = slog::Never, Ok = ()> + Send + 'static,
+    {
+        let builder = Async::new(drain).overflow_strategy(self.overflow.into());
+
+        let builder = match self.channel_size {
+            Some(size) => builder.chan_size(size),
+            None => builder,
+        };
+
+        match self.thread_name {
+            Some(ref name) => builder.thread_name(name.clone()),
+            None => builder,
+        }
+    }
+}
+
+
+/// What to do with records sent to an `Async` drain while its channel is full.
+///
+/// Defaults to [`DropAndReport`](Overflow::DropAndReport).
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overflow {
+    /// Block until the record can be sent.
+    Block,
+
+    /// Silently drop the record.
+    Drop,
+
+    /// Drop the record and report the number of dropped records as a warning
+    /// record once the channel has space again.
+    DropAndReport,
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Overflow::DropAndReport
+    }
+}
+
+impl From<Overflow> for OverflowStrategy {
+    fn from(overflow: Overflow) -> Self {
+        match overflow {
+            Overflow::Block => OverflowStrategy::Block,
+            Overflow::Drop => OverflowStrategy::Drop,
+            Overflow::DropAndReport => OverflowStrategy::DropAndReport,
+        }
+    }
+}
+
 /// A `slog::Value`-implementation displaying the tag of the log-record if it is
 /// not empty.
 pub struct OptionalTag;
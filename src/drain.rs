@@ -0,0 +1,556 @@
+//! Drain implementations used by the default factories.
+
+use Error;
+use common::{is_submodule, Directives};
+use output::Dropped;
+use output::journal::Priority;
+use signal::{Signal, Watch};
+
+use std;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use slog::{self, Drain, Level, OwnedKVList, Record};
+
+
+/// A `Drain` filtering records by their level and module.
+///
+/// Records are filtered according to a set of [`Directives`](::common::Directives),
+/// i.e. with the level of the longest module path matching the module of the
+/// record, or the default level if no module path matches. The directives can
+/// be overridden at runtime via a [`LevelHandle`](LevelHandle).
+///
+/// In contrast to `slog::LevelFilter`, this drain does not change the `Ok`-type
+/// of the wrapped drain, thus it can be used to build a
+/// [`BoxDrain`](::BoxDrain).
+#[derive(Debug, Clone)]
+pub struct LevelFilter<D> {
+    drain:   D,
+    default: Level,
+    modules: Vec<(String, Level)>,
+    max:     Level,
+    handle:  Option<Attached>,
+}
+
+/// A handle attached to a filter.
+///
+/// The filter counts towards the base level of the handle as long as the
+/// token (or a clone of it) is alive.
+#[derive(Debug, Clone)]
+struct Attached {
+    handle: LevelHandle,
+    _token: Arc<()>,
+}
+
+impl<D: Drain> LevelFilter<D> {
+    /// Creates a new filter passing on all records allowed by the specified
+    /// directives (or level) to the given drain.
+    pub fn new<L>(drain: D, directives: L) -> Self
+    where
+        L: Into<Directives>,
+    {
+        let directives = directives.into();
+        let default: Level = directives.default.into();
+
+        // sorted by descending length, the first match is the longest one
+        let mut modules = directives
+            .modules
+            .into_iter()
+            .map(|(module, level)| (module, level.into()))
+            .collect::<Vec<(String, Level)>>();
+
+        modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        let max = modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(default, |max, level| if max.is_at_least(level) { level } else { max });
+
+        LevelFilter {
+            drain,
+            default,
+            modules,
+            max,
+            handle: None,
+        }
+    }
+
+    /// Attaches the specified handle to this filter, allowing the directives
+    /// of this filter to be overridden at runtime.
+    pub fn with_handle(mut self, handle: LevelHandle) -> Self {
+        let token = handle.attach(self.default);
+        self.handle = Some(Attached { handle, _token: token });
+        self
+    }
+
+    fn level(&self, module: &str) -> Level {
+        self.modules
+            .iter()
+            .find(|&&(ref path, _)| is_submodule(module, path))
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn overridden(&self) -> Option<Level> {
+        self.handle.as_ref().and_then(|attached| attached.handle.overridden())
+    }
+}
+
+impl<D: Drain<Ok = ()>> Drain for LevelFilter<D> {
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), D::Err> {
+        let level = match self.overridden() {
+            Some(level) => level,
+            None => self.level(record.module()),
+        };
+
+        if record.level().is_at_least(level) {
+            self.drain.log(record, values)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        let max = self.overridden().unwrap_or(self.max);
+        level.is_at_least(max) && self.drain.is_enabled(level)
+    }
+}
+
+
+/// A `Drain` setting a shared [`Priority`](::output::journal::Priority) to
+/// the level of each record before passing it on.
+///
+/// Used together with a [`PriorityPrefix`](::output::journal::PriorityPrefix)
+/// writer to prefix the lines of each record with its `sd-daemon` priority.
+/// The wrapped drain must write records synchronously, i.e. it must not be an
+/// `Async` drain. Records are logged one at a time.
+#[derive(Debug)]
+pub struct Prioritize<D> {
+    drain:    D,
+    priority: Priority,
+    lock:     Mutex<()>,
+}
+
+impl<D: Drain> Prioritize<D> {
+    /// Creates a new drain setting the specified priority.
+    pub fn new(drain: D, priority: Priority) -> Self {
+        Prioritize {
+            drain,
+            priority,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl<D: Drain> Drain for Prioritize<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<D::Ok, D::Err> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        self.priority.set(record.level().into());
+        self.drain.log(record, values)
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+}
+
+
+/// A `Drain` discarding the records its wrapped drain fails to log.
+///
+/// Errors of the wrapped drain are not passed on, thus this drain can safely
+/// be used in combination with other drains or in an `Async` drain. Instead,
+/// the discarded records are counted. Once the wrapped drain logs a record
+/// successfully again, a warning record reporting the number of discarded
+/// records and the last error is logged to it.
+#[derive(Debug)]
+pub struct Discarding<D> {
+    drain:     D,
+    discarded: Mutex<Discarded>,
+}
+
+#[derive(Debug, Default)]
+struct Discarded {
+    count: u64,
+    error: String,
+}
+
+impl<D: Drain> Discarding<D> {
+    /// Creates a new drain discarding records the given drain fails to log.
+    pub fn new(drain: D) -> Self {
+        Discarding {
+            drain,
+            discarded: Mutex::new(Discarded::default()),
+        }
+    }
+
+    /// Returns the number of records discarded since the last report.
+    pub fn discarded(&self) -> u64 {
+        self.state().count
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, Discarded> {
+        self.discarded.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<D> Drain for Discarding<D>
+where
+    D: Drain<Ok = ()>,
+    D::Err: std::fmt::Display,
+{
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+        if let Err(e) = self.drain.log(record, values) {
+            let mut discarded = self.state();
+            discarded.count += 1;
+            discarded.error = e.to_string();
+            return Ok(());
+        }
+
+        let discarded = std::mem::take(&mut *self.state());
+        if discarded.count == 0 {
+            return Ok(());
+        }
+
+        let reported = report_dropped(discarded.count, &discarded.error, |record, values| {
+            self.drain.log(record, values)
+        });
+
+        if let Err(e) = reported {
+            let mut state = self.state();
+            state.count += discarded.count;
+            state.error = e.to_string();
+        }
+
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+}
+
+
+/// A `Drain` reporting the records dropped by the output of its wrapped drain.
+///
+/// After each record, the records dropped by the output since the last report
+/// (see [`Dropped`](::output::Dropped)) are reported by logging a warning
+/// record to the wrapped drain. Thus, the report is written in the format of
+/// the wrapped drain once the output is able to deliver records again.
+#[derive(Debug)]
+pub struct Reporting<D> {
+    drain:   D,
+    dropped: Dropped,
+}
+
+impl<D: Drain> Reporting<D> {
+    /// Creates a new drain reporting the records dropped by the given output.
+    pub fn new(drain: D, dropped: Dropped) -> Self {
+        Reporting { drain, dropped }
+    }
+}
+
+impl<D: Drain> Drain for Reporting<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<D::Ok, D::Err> {
+        let result = self.drain.log(record, values)?;
+
+        // the output may have reconnected while logging the record
+        if let Some((count, reason)) = self.dropped.take() {
+            report_dropped(count, &reason, |record, values| self.drain.log(record, values))?;
+        }
+
+        Ok(result)
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+}
+
+
+/// Passes a warning record reporting the given number of dropped records and
+/// the reason for dropping them to the specified closure.
+///
+/// The default drains use this to report dropped records in-band, i.e. as
+/// part of their regular output, once they are able to deliver records again.
+pub(crate) fn report_dropped<F, R>(count: u64, reason: &str, report: F) -> R
+where
+    F: FnOnce(&Record, &OwnedKVList) -> R,
+{
+    let values = OwnedKVList::from(o!());
+
+    report(
+        &record!(
+            Level::Warning,
+            "",
+            &format_args!("dropped {} records", count),
+            b!("dropped" => count, "reason" => reason)
+        ),
+        &values,
+    )
+}
+
+
+/// A cloneable handle to adjust the level of one or more
+/// [`LevelFilter`s](LevelFilter) at runtime.
+///
+/// Setting a level via this handle overrides the directives of all attached
+/// filters, i.e. records from all modules are filtered with this level, until
+/// the handle is reset.
+///
+/// The default factories attach the handle with the name specified in the
+/// `handle` field of a configuration. Handles are collected by the context in
+/// which the configuration is built and can be retrieved by their name via
+/// [`Context::handle`](::Context::handle).
+#[derive(Debug, Clone)]
+pub struct LevelHandle {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// The overriding level, `0` if the directives apply.
+    level: AtomicUsize,
+
+    /// The default levels of all attached filters, a filter is detached once
+    /// its token has been dropped.
+    attached: Mutex<Vec<(Weak<()>, Level)>>,
+
+    /// `true` while an escalation is active.
+    escalated: AtomicBool,
+
+    /// Serializes changes of the level and tracks temporary escalations.
+    state: Mutex<State>,
+
+    bound:   AtomicBool,
+    signals: Mutex<Option<(Watch, Watch)>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The level to restore after an escalation and the time at which it
+    /// should be restored, if an escalation is active.
+    restore: Option<(usize, Instant)>,
+}
+
+impl LevelHandle {
+    /// Creates a new handle that is not attached to any filter.
+    pub fn new() -> Self {
+        LevelHandle {
+            shared: Arc::new(Shared {
+                level: AtomicUsize::new(0),
+                attached: Mutex::new(Vec::new()),
+                escalated: AtomicBool::new(false),
+                state: Mutex::new(State::default()),
+                bound: AtomicBool::new(false),
+                signals: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Returns the current level of this handle.
+    ///
+    /// This is the overriding level if one has been set, otherwise the most
+    /// verbose default level of all attached filters (or `Info` if no filter
+    /// is attached).
+    pub fn level(&self) -> Level {
+        self.overridden()
+            .or_else(|| self.base())
+            .unwrap_or(Level::Info)
+    }
+
+    /// Returns `true` if the directives of the attached filters are currently
+    /// being overridden.
+    pub fn is_overridden(&self) -> bool {
+        self.expire();
+        self.shared.level.load(Ordering::SeqCst) != 0
+    }
+
+    /// Overrides the directives of all attached filters with the specified
+    /// level.
+    ///
+    /// Cancels any active escalation.
+    pub fn set(&self, level: Level) {
+        self.store(level.as_usize())
+    }
+
+    /// Removes any override, so that the directives of the attached filters
+    /// apply again.
+    ///
+    /// Cancels any active escalation.
+    pub fn reset(&self) {
+        self.store(0)
+    }
+
+    /// Temporarily overrides the directives of all attached filters with the
+    /// specified level.
+    ///
+    /// After the given duration has elapsed, the state before the escalation
+    /// is restored. A subsequent escalation replaces the current one, but
+    /// still restores the state before the first one. Setting or resetting
+    /// the level cancels the escalation.
+    ///
+    /// No thread is involved, instead the end of the escalation is checked
+    /// whenever the level is queried, i.e. by the attached filters with each
+    /// record.
+    pub fn escalate(&self, level: Level, duration: Duration) {
+        let mut state = self.state();
+        let deadline = Instant::now() + duration;
+
+        let restore = match state.restore {
+            Some((restore, _)) => restore,
+            None => self.shared.level.load(Ordering::SeqCst),
+        };
+
+        state.restore = Some((restore, deadline));
+        self.shared.level.store(level.as_usize(), Ordering::SeqCst);
+        self.shared.escalated.store(true, Ordering::SeqCst);
+    }
+
+    /// Increases the verbosity of all attached filters by one level, starting
+    /// at the current level of this handle.
+    ///
+    /// Cancels any active escalation.
+    pub fn step_up(&self) {
+        self.step(1)
+    }
+
+    /// Decreases the verbosity of all attached filters by one level, starting
+    /// at the current level of this handle.
+    ///
+    /// Cancels any active escalation.
+    pub fn step_down(&self) {
+        self.step(-1)
+    }
+
+    /// Binds this handle to the `SIGUSR1` and `SIGUSR2` signals.
+    ///
+    /// On `SIGUSR1` the verbosity is increased by one level, on `SIGUSR2` it
+    /// is decreased by one level (see [`step_up`](LevelHandle::step_up) and
+    /// [`step_down`](LevelHandle::step_down)). Signals are processed by the
+    /// attached filters, i.e. take effect with the next record.
+    ///
+    /// Returns an error if signals are not supported on this platform or the
+    /// `signal` feature is disabled.
+    pub fn bind_signals(&self) -> Result<(), Error> {
+        let mut signals = self.shared.signals.lock().unwrap_or_else(|e| e.into_inner());
+
+        if signals.is_none() {
+            *signals = Some((Watch::new(Signal::Usr1)?, Watch::new(Signal::Usr2)?));
+            self.shared.bound.store(true, Ordering::SeqCst);
+        }
+
+        Ok(())
+    }
+
+    fn step(&self, steps: isize) {
+        let current = self.level().as_usize() as isize;
+        let level = std::cmp::min(std::cmp::max(current + steps, 1), 6);
+
+        if let Some(level) = Level::from_usize(level as usize) {
+            self.set(level);
+        }
+    }
+
+    /// Attaches a filter with the specified default level, returning the
+    /// token keeping it attached.
+    fn attach(&self, default: Level) -> Arc<()> {
+        let token = Arc::new(());
+
+        let mut attached = self.attached();
+        attached.retain(|(token, _)| token.upgrade().is_some());
+        attached.push((Arc::downgrade(&token), default));
+
+        token
+    }
+
+    /// Returns the most verbose default level of all attached filters.
+    fn base(&self) -> Option<Level> {
+        let mut attached = self.attached();
+        attached.retain(|(token, _)| token.upgrade().is_some());
+
+        attached
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(None, |max, level| match max {
+                Some(max) if max.is_at_least(level) => Some(level),
+                Some(max) => Some(max),
+                None => Some(level),
+            })
+    }
+
+    fn store(&self, level: usize) {
+        let mut state = self.state();
+        state.restore = None;
+
+        self.shared.level.store(level, Ordering::SeqCst);
+        self.shared.escalated.store(false, Ordering::SeqCst);
+    }
+
+    /// Restores the state before an escalation if its duration has elapsed.
+    fn expire(&self) {
+        if !self.shared.escalated.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut state = self.state();
+
+        if let Some((restore, deadline)) = state.restore {
+            if Instant::now() >= deadline {
+                state.restore = None;
+
+                self.shared.level.store(restore, Ordering::SeqCst);
+                self.shared.escalated.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn overridden(&self) -> Option<Level> {
+        if self.shared.bound.load(Ordering::Relaxed) {
+            self.poll_signals();
+        }
+
+        self.expire();
+        Level::from_usize(self.shared.level.load(Ordering::SeqCst))
+    }
+
+    fn poll_signals(&self) {
+        let steps = match self.shared.signals.try_lock() {
+            Ok(mut signals) => match *signals {
+                Some((ref mut up, ref mut down)) => up.poll() as isize - down.poll() as isize,
+                None => 0,
+            },
+            Err(_) => 0,
+        };
+
+        if steps != 0 {
+            self.step(steps);
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn attached(&self) -> std::sync::MutexGuard<'_, Vec<(Weak<()>, Level)>> {
+        self.shared.attached.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for LevelHandle {
+    fn default() -> Self {
+        LevelHandle::new()
+    }
+}
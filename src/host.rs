@@ -0,0 +1,46 @@
+//! Information about the host and the current process.
+
+use std;
+
+
+/// Returns the file name of the current executable.
+#[cfg(any(feature = "json-bunyan", feature = "syslog", feature = "journald"))]
+pub fn name() -> Option<String> {
+    let path = std::env::current_exe().ok();
+    let name = path.as_ref().and_then(|path| path.file_name());
+
+    name.and_then(|name| name.to_str()).map(|name| name.to_owned())
+}
+
+/// Returns the name of the host.
+#[cfg(any(feature = "json-bunyan", feature = "syslog", feature = "gelf"))]
+pub fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    use libc::gethostname;
+
+    #[cfg(windows)]
+    use winapi::um::winsock2::gethostname;
+
+    const MAXLEN: usize = 256;
+
+    let mut buf = [0 as std::os::raw::c_char; MAXLEN];
+    let err = unsafe { gethostname(buf.as_mut_ptr() as *mut _, MAXLEN as _) };
+
+    if err == 0 {
+        unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()).to_str().ok().map(|name| name.to_owned()) }
+    } else {
+        None
+    }
+}
+
+/// Returns the ID of the current process.
+#[cfg(any(feature = "json-bunyan", feature = "syslog", feature = "gelf"))]
+pub fn pid() -> u64 {
+    #[cfg(unix)]
+    use libc::getpid;
+
+    #[cfg(windows)]
+    use winapi::um::processthreadsapi::GetCurrentProcessId as getpid;
+
+    unsafe { getpid() as u64 }
+}
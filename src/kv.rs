@@ -0,0 +1,31 @@
+//! Collection of the key-value pairs of records.
+
+use std::fmt;
+
+use slog::{self, Key, OwnedKVList, Record, KV};
+
+
+/// Returns all key-value pairs of the specified record and logger values as
+/// strings.
+///
+/// The pairs of the record come first, followed by the pairs of the logger
+/// values, each in the order in which they would be serialized by slog.
+pub fn collect(record: &Record, values: &OwnedKVList) -> Vec<(String, String)> {
+    let mut collector = Collector(Vec::new());
+
+    // collecting into a vector never fails
+    let _ = record.kv().serialize(record, &mut collector);
+    let _ = values.serialize(record, &mut collector);
+
+    collector.0
+}
+
+
+struct Collector(Vec<(String, String)>);
+
+impl slog::Serializer for Collector {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.push((key.to_string(), val.to_string()));
+        Ok(())
+    }
+}
@@ -36,13 +36,29 @@
 //! via the [`factories`](factories)-method. [`build`](build) is a
 //! convenience-method using this default registry to build a `Drain`.
 //!
+//! A second default registry, provided via the [`drains`](drains)-method,
+//! builds synchronous drains (see [`BoxDrain`](BoxDrain)) instead of `Async`
+//! drains. The default factories of composite configuration types (e.g.
+//! `multi` or `route`) use this registry to build the drains of their nested
+//! configurations, before wrapping the combined drain in a single `Async`
+//! drain. A different registry can be used for nested configurations by
+//! building within a custom [`Context`](Context).
+//!
+//! The worker thread of the `Async` drain can be configured via the `async`
+//! section accepted by all default configuration types (see
+//! [`AsyncWorker`](common::AsyncWorker)), e.g. its channel size and whether
+//! records should be dropped if the channel is full.
+//!
 //! ## Customizable Features for Compile-Time Configuration
 //!
 //! The configuration types and default factories supported by this crate can
 //! be found in the [`ty`](ty) module and can be configured via the
 //! feature-set of this crate. For each supported type exists is a
 //! corresponding feature with the same name enabling support for said type.
-//! By default, all types are enabled.
+//! By default, the `null`, `plain`, `term`, and `json` types (including the
+//! `json-bunyan` format) are enabled. All other types, as well as file
+//! compression (`gzip`, `zstd`) and reopening files on signals (`signal`),
+//! have to be enabled explicitly.
 
 extern crate serde;
 extern crate serde_tagged;
@@ -58,7 +74,7 @@ extern crate failure;
 #[macro_use]
 extern crate lazy_static;
 
-#[cfg_attr(feature = "json", macro_use)]
+#[macro_use]
 extern crate slog;
 extern crate slog_async;
 
@@ -68,17 +84,40 @@ extern crate slog_term;
 #[cfg(feature = "json")]
 extern crate slog_json;
 
-#[cfg(all(unix, feature = "json-bunyan"))]
+#[cfg(any(
+    feature = "gelf",
+    feature = "http",
+    feature = "csv",
+    feature = "binary",
+    feature = "sqlite"
+))]
+#[cfg_attr(feature = "http", macro_use)]
+extern crate serde_json;
+
+#[cfg(all(unix, feature = "libc"))]
 extern crate libc;
 
-#[cfg(all(windows, feature = "json-bunyan"))]
+#[cfg(all(windows, feature = "winapi"))]
 extern crate winapi;
 
+#[cfg(all(unix, feature = "signal"))]
+extern crate signal_hook;
+
 extern crate chrono;
 
+#[cfg(feature = "gzip")]
+extern crate flate2;
+
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+
 
 use std::any::TypeId;
 use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 
 use serde::{Deserialize, Serialize};
 use serde::de::DeserializeSeed;
@@ -88,21 +127,168 @@ use serde_tagged::util::erased::SerializeErased;
 
 use slog_async::{Async, AsyncGuard};
 
+use common::Target;
+use drain::LevelHandle;
+use output::{Opened, Outputs};
+
 
 pub mod common;
+pub mod drain;
+pub mod output;
+pub mod reload;
 pub mod ty;
 
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(feature = "binary")]
+mod cbor;
+#[cfg(any(feature = "json-bunyan", feature = "syslog", feature = "journald", feature = "gelf"))]
+mod host;
+#[cfg(any(
+    feature = "syslog",
+    feature = "journald",
+    feature = "gelf",
+    feature = "fluent",
+    feature = "http",
+    feature = "spool",
+    feature = "logfmt",
+    feature = "csv",
+    feature = "sqlite"
+))]
+mod kv;
+#[cfg(any(feature = "fluent", feature = "spool", feature = "binary"))]
+mod msgpack;
+mod net;
+mod signal;
+
 #[cfg(feature = "null")]
-pub use ty::null::{Config as NullConfig, Factory as NullFactory};
+pub use ty::null::{
+    Config as NullConfig,
+    DrainFactory as NullDrainFactory,
+    Factory as NullFactory,
+};
 
 #[cfg(feature = "plain")]
-pub use ty::plain::{Config as PlainConfig, Factory as PlainFactory};
+pub use ty::plain::{
+    Config as PlainConfig,
+    DrainFactory as PlainDrainFactory,
+    Factory as PlainFactory,
+};
 
 #[cfg(feature = "term")]
-pub use ty::term::{Config as TermConfig, Factory as TermFactory};
+pub use ty::term::{
+    Config as TermConfig,
+    DrainFactory as TermDrainFactory,
+    Factory as TermFactory,
+};
 
 #[cfg(feature = "json")]
-pub use ty::json::{Config as JsonConfig, Factory as JsonFactory};
+pub use ty::json::{
+    Config as JsonConfig,
+    DrainFactory as JsonDrainFactory,
+    Factory as JsonFactory,
+};
+
+#[cfg(feature = "logfmt")]
+pub use ty::logfmt::{
+    Config as LogfmtConfig,
+    DrainFactory as LogfmtDrainFactory,
+    Factory as LogfmtFactory,
+};
+
+#[cfg(feature = "csv")]
+pub use ty::csv::{
+    Config as CsvConfig,
+    DrainFactory as CsvDrainFactory,
+    Factory as CsvFactory,
+};
+
+#[cfg(feature = "binary")]
+pub use ty::binary::{
+    Config as BinaryConfig,
+    DrainFactory as BinaryDrainFactory,
+    Factory as BinaryFactory,
+};
+
+#[cfg(feature = "sqlite")]
+pub use ty::sqlite::{
+    Config as SqliteConfig,
+    DrainFactory as SqliteDrainFactory,
+    Factory as SqliteFactory,
+};
+
+#[cfg(feature = "multi")]
+pub use ty::multi::{
+    Config as MultiConfig,
+    DrainFactory as MultiDrainFactory,
+    Factory as MultiFactory,
+};
+
+#[cfg(feature = "route")]
+pub use ty::route::{
+    Config as RouteConfig,
+    DrainFactory as RouteDrainFactory,
+    Factory as RouteFactory,
+};
+
+#[cfg(feature = "fallback")]
+pub use ty::fallback::{
+    Config as FallbackConfig,
+    DrainFactory as FallbackDrainFactory,
+    Factory as FallbackFactory,
+};
+
+#[cfg(feature = "auto")]
+pub use ty::auto::{
+    Config as AutoConfig,
+    DrainFactory as AutoDrainFactory,
+    Factory as AutoFactory,
+};
+
+#[cfg(feature = "spool")]
+pub use ty::spool::{
+    Config as SpoolConfig,
+    DrainFactory as SpoolDrainFactory,
+    Factory as SpoolFactory,
+};
+
+#[cfg(feature = "syslog")]
+pub use ty::syslog::{
+    Config as SyslogConfig,
+    DrainFactory as SyslogDrainFactory,
+    Factory as SyslogFactory,
+};
+
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub use ty::journald::{
+    Config as JournaldConfig,
+    DrainFactory as JournaldDrainFactory,
+    Factory as JournaldFactory,
+};
+
+#[cfg(feature = "gelf")]
+pub use ty::gelf::{
+    Config as GelfConfig,
+    DrainFactory as GelfDrainFactory,
+    Factory as GelfFactory,
+};
+
+#[cfg(feature = "fluent")]
+pub use ty::fluent::{
+    Config as FluentConfig,
+    DrainFactory as FluentDrainFactory,
+    Factory as FluentFactory,
+};
+
+#[cfg(feature = "http")]
+pub use ty::http::{
+    Config as HttpConfig,
+    DrainFactory as HttpDrainFactory,
+    Factory as HttpFactory,
+};
 
 
 /// The name of the field containing the type of a serialized logger
@@ -124,6 +310,34 @@ pub const SUPPORTED_TYPES: &[&str] = &[
     "term",
     #[cfg(feature = "json")]
     "json",
+    #[cfg(feature = "logfmt")]
+    "logfmt",
+    #[cfg(feature = "csv")]
+    "csv",
+    #[cfg(feature = "binary")]
+    "binary",
+    #[cfg(feature = "sqlite")]
+    "sqlite",
+    #[cfg(feature = "multi")]
+    "multi",
+    #[cfg(feature = "route")]
+    "route",
+    #[cfg(feature = "fallback")]
+    "fallback",
+    #[cfg(feature = "auto")]
+    "auto",
+    #[cfg(feature = "spool")]
+    "spool",
+    #[cfg(feature = "syslog")]
+    "syslog",
+    #[cfg(all(target_os = "linux", feature = "journald"))]
+    "journald",
+    #[cfg(feature = "gelf")]
+    "gelf",
+    #[cfg(feature = "fluent")]
+    "fluent",
+    #[cfg(feature = "http")]
+    "http",
 ];
 
 /// Returns a reference to the default deserializer-stub registry.
@@ -159,6 +373,24 @@ pub fn factories() -> &'static Factories<(Async, AsyncGuard)> {
     &REG
 }
 
+/// Returns a reference to the default synchronous `Drain` factories.
+///
+/// The default drain factories will create a [`BoxDrain`](::BoxDrain). They are
+/// used by the default factories of composite configuration types to build the
+/// drains of their nested configurations.
+///
+/// The set of supported configuration types can be configured by the feature
+/// set of this crate.
+///
+/// See [`Factories`](::Factories) for more information.
+pub fn drains() -> &'static Factories<BoxDrain> {
+    lazy_static! {
+        static ref REG: Factories<BoxDrain> = Factories::default();
+    }
+
+    &REG
+}
+
 /// Builds a `Drain` from the given `Config` using the default factories.
 ///
 /// This will create an `Async` drain as well as its `AsyncGuard` and is
@@ -169,6 +401,24 @@ pub fn build(cfg: &Config) -> Result<(Async, AsyncGuard), Error> {
     factories().build(cfg)
 }
 
+/// Builds a synchronous `Drain` from the given `Config` using the default drain
+/// factories.
+///
+/// This is equivalent to `drains().build(cfg)`.
+///
+/// See [`drains()`](::drains) for more information.
+pub fn build_drain(cfg: &Config) -> Result<BoxDrain, Error> {
+    drains().build(cfg)
+}
+
+
+/// A boxed, synchronous `Drain`.
+///
+/// This is the target type of the default drain factories (see
+/// [`drains()`](::drains)). Errors are passed on to the caller, combining
+/// drains or handling errors (e.g. via `Drain::fuse`) is up to the user.
+pub type BoxDrain = Box<slog::Drain<Ok = (), Err = std::io::Error> + Send>;
+
 
 #[allow(unused_imports)]
 #[allow(unused_mut)]
@@ -192,6 +442,48 @@ impl Default for Deserializers {
         #[cfg(feature = "json")]
         reg.register("json", JsonConfig::deserialize_config);
 
+        #[cfg(feature = "logfmt")]
+        reg.register("logfmt", LogfmtConfig::deserialize_config);
+
+        #[cfg(feature = "csv")]
+        reg.register("csv", CsvConfig::deserialize_config);
+
+        #[cfg(feature = "binary")]
+        reg.register("binary", BinaryConfig::deserialize_config);
+
+        #[cfg(feature = "sqlite")]
+        reg.register("sqlite", SqliteConfig::deserialize_config);
+
+        #[cfg(feature = "multi")]
+        reg.register("multi", MultiConfig::deserialize_config);
+
+        #[cfg(feature = "route")]
+        reg.register("route", RouteConfig::deserialize_config);
+
+        #[cfg(feature = "fallback")]
+        reg.register("fallback", FallbackConfig::deserialize_config);
+
+        #[cfg(feature = "auto")]
+        reg.register("auto", AutoConfig::deserialize_config);
+
+        #[cfg(feature = "spool")]
+        reg.register("spool", SpoolConfig::deserialize_config);
+
+        #[cfg(feature = "syslog")]
+        reg.register("syslog", SyslogConfig::deserialize_config);
+
+        #[cfg(all(target_os = "linux", feature = "journald"))]
+        reg.register("journald", JournaldConfig::deserialize_config);
+
+        #[cfg(feature = "gelf")]
+        reg.register("gelf", GelfConfig::deserialize_config);
+
+        #[cfg(feature = "fluent")]
+        reg.register("fluent", FluentConfig::deserialize_config);
+
+        #[cfg(feature = "http")]
+        reg.register("http", HttpConfig::deserialize_config);
+
         reg
     }
 }
@@ -218,6 +510,116 @@ impl Default for Factories<(Async, AsyncGuard)> {
         #[cfg(feature = "json")]
         reg.register(JsonFactory);
 
+        #[cfg(feature = "logfmt")]
+        reg.register(LogfmtFactory);
+
+        #[cfg(feature = "csv")]
+        reg.register(CsvFactory);
+
+        #[cfg(feature = "binary")]
+        reg.register(BinaryFactory);
+
+        #[cfg(feature = "sqlite")]
+        reg.register(SqliteFactory);
+
+        #[cfg(feature = "multi")]
+        reg.register(MultiFactory);
+
+        #[cfg(feature = "route")]
+        reg.register(RouteFactory);
+
+        #[cfg(feature = "fallback")]
+        reg.register(FallbackFactory);
+
+        #[cfg(feature = "auto")]
+        reg.register(AutoFactory);
+
+        #[cfg(feature = "spool")]
+        reg.register(SpoolFactory);
+
+        #[cfg(feature = "syslog")]
+        reg.register(SyslogFactory);
+
+        #[cfg(all(target_os = "linux", feature = "journald"))]
+        reg.register(JournaldFactory);
+
+        #[cfg(feature = "gelf")]
+        reg.register(GelfFactory);
+
+        #[cfg(feature = "fluent")]
+        reg.register(FluentFactory);
+
+        #[cfg(feature = "http")]
+        reg.register(HttpFactory);
+
+        reg
+    }
+}
+
+#[allow(unused_mut)]
+#[cfg_attr(feature = "cargo-clippy", allow(let_and_return))]
+impl Default for Factories<BoxDrain> {
+    /// Returns a registry containing default drain factories for all supported
+    /// configuration-types.
+    ///
+    /// See [`ty`](::ty) for the default factories.
+    fn default() -> Self {
+        let mut reg = Factories::empty();
+
+        #[cfg(feature = "null")]
+        reg.register(NullDrainFactory);
+
+        #[cfg(feature = "plain")]
+        reg.register(PlainDrainFactory);
+
+        #[cfg(feature = "term")]
+        reg.register(TermDrainFactory);
+
+        #[cfg(feature = "json")]
+        reg.register(JsonDrainFactory);
+
+        #[cfg(feature = "logfmt")]
+        reg.register(LogfmtDrainFactory);
+
+        #[cfg(feature = "csv")]
+        reg.register(CsvDrainFactory);
+
+        #[cfg(feature = "binary")]
+        reg.register(BinaryDrainFactory);
+
+        #[cfg(feature = "sqlite")]
+        reg.register(SqliteDrainFactory);
+
+        #[cfg(feature = "multi")]
+        reg.register(MultiDrainFactory);
+
+        #[cfg(feature = "route")]
+        reg.register(RouteDrainFactory);
+
+        #[cfg(feature = "fallback")]
+        reg.register(FallbackDrainFactory);
+
+        #[cfg(feature = "auto")]
+        reg.register(AutoDrainFactory);
+
+        #[cfg(feature = "spool")]
+        reg.register(SpoolDrainFactory);
+
+        #[cfg(feature = "syslog")]
+        reg.register(SyslogDrainFactory);
+
+        #[cfg(all(target_os = "linux", feature = "journald"))]
+        reg.register(JournaldDrainFactory);
+
+        #[cfg(feature = "gelf")]
+        reg.register(GelfDrainFactory);
+
+        #[cfg(feature = "fluent")]
+        reg.register(FluentDrainFactory);
+
+        #[cfg(feature = "http")]
+        reg.register(HttpDrainFactory);
+
         reg
     }
 }
@@ -367,6 +769,17 @@ pub trait Factory {
 
     /// Builds a `Target` from the specified configuration.
     fn build(&self, cfg: &Self::Config) -> Result<Self::Target, Error>;
+
+    /// Builds a `Target` from the specified configuration within the given
+    /// context.
+    ///
+    /// Factories of composite configuration types should build the drains of
+    /// their nested configurations via the context (see
+    /// [`Context::build`](Context::build)). The default implementation ignores
+    /// the context and calls [`build`](Factory::build).
+    fn build_in(&self, cfg: &Self::Config, _ctx: &Context) -> Result<Self::Target, Error> {
+        self.build(cfg)
+    }
 }
 
 
@@ -375,8 +788,9 @@ trait FactoryShim: Sync {
     /// The target type that will be built by this factory-shim.
     type Target;
 
-    /// Builds a `Target` from the specified configuration-object.
-    fn build(&self, cfg: &Config) -> Result<Self::Target, Error>;
+    /// Builds a `Target` from the specified configuration-object within the
+    /// given context.
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error>;
 }
 
 /// A `FactoryShim` implementation that panics on an invalid trait-object
@@ -386,9 +800,9 @@ struct Unchecked<F>(F);
 impl<F: Factory + Sync> FactoryShim for Unchecked<F> {
     type Target = F::Target;
 
-    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
         let cfg = cfg.downcast_ref::<F::Config>().expect("invalid cast");
-        self.0.build(cfg)
+        self.0.build_in(cfg, ctx)
     }
 }
 
@@ -480,11 +894,145 @@ impl<T> Factories<T> {
     /// nontheless, this may indicate that either the default implementation of
     /// [`Config::type_id()`](::Config::type_id) has been manually overwritten
     /// or a collision of two `TypeId`s has occured.
+    ///
+    /// Nested configurations are built via the default drain factories (see
+    /// [`Context::default`](Context::default)).
     pub fn build(&self, cfg: &Config) -> Result<T, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    /// Build a `T` from the specified `Config`-object within the given
+    /// context.
+    ///
+    /// This works like [`build`](Factories::build), except that the `build_in`
+    /// method of the factory is invoked with the given context, i.e. nested
+    /// configurations are built via the registry of the context.
+    pub fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<T, Error> {
         self.store
             .get(&cfg.type_id())
             .ok_or_else(|| Error::Unsupported)?
-            .build(cfg)
+            .build_in(cfg, ctx)
+    }
+}
+
+
+/// The context in which a target is built by a factory.
+///
+/// The context provides the registry via which factories of composite
+/// configuration types (e.g. `multi` or `route`) build the drains of their
+/// nested configurations. Use [`Factories::build_in`](Factories::build_in)
+/// to build a configuration with custom drain factories for all of its
+/// nested configurations.
+///
+/// Furthermore, the context collects the level handles attached to the
+/// drains built within it (see [`handle`](Context::handle)) and the outputs
+/// opened by them (see [`open`](Context::open)).
+pub struct Context<'a> {
+    drains:   &'a Factories<BoxDrain>,
+    handles:  Mutex<BTreeMap<String, LevelHandle>>,
+    reusable: Outputs,
+    outputs:  Mutex<Outputs>,
+}
+
+impl<'a> Context<'a> {
+    /// Creates a new context building nested drains via the specified
+    /// registry.
+    pub fn new(drains: &'a Factories<BoxDrain>) -> Self {
+        Context {
+            drains,
+            handles: Mutex::new(BTreeMap::new()),
+            reusable: Outputs::new(),
+            outputs: Mutex::new(Outputs::new()),
+        }
+    }
+
+    /// Adds the specified level handles to this context, so that they are
+    /// attached to the drains built within it instead of new handles.
+    ///
+    /// This allows a handle to be kept across multiple builds, e.g. when
+    /// reloading a configuration.
+    pub fn with_handles<I>(self, handles: I) -> Self
+    where
+        I: IntoIterator<Item = (String, LevelHandle)>,
+    {
+        self.lock_handles().extend(handles);
+        self
+    }
+
+    /// Provides the outputs of a previous build, so that identically
+    /// configured targets continue to use their writers instead of being
+    /// opened again (see [`Outputs`](output::Outputs)).
+    pub fn with_outputs(mut self, outputs: Outputs) -> Self {
+        self.reusable = outputs;
+        self
+    }
+
+    /// Returns the registry via which nested drains are built.
+    pub fn drains(&self) -> &'a Factories<BoxDrain> {
+        self.drains
+    }
+
+    /// Opens a writer for the specified target (see
+    /// [`output::open`](output::open)), reusing an output of a previous build
+    /// if possible.
+    ///
+    /// Records dropped by the writer (e.g. while a network endpoint is
+    /// unavailable) can be reported via its [`Dropped`](output::Dropped)
+    /// handle.
+    pub fn open(&self, target: &Target) -> Result<Opened, Error> {
+        self.lock_outputs().open(&self.reusable, target, None)
+    }
+
+    /// Opens a writer for the specified target, writing the given header at
+    /// the start of the output (see
+    /// [`output::open_with_header`](output::open_with_header)), reusing an
+    /// output of a previous build if possible.
+    pub fn open_with_header(&self, target: &Target, header: &[u8]) -> Result<Opened, Error> {
+        self.lock_outputs().open(&self.reusable, target, Some(header))
+    }
+
+    /// Returns the outputs opened within this context.
+    pub fn outputs(&self) -> Outputs {
+        self.lock_outputs().clone()
+    }
+
+    /// Returns the level handle with the specified name, creating it if this
+    /// context does not contain it yet.
+    ///
+    /// The default factories attach the handle named in the `handle` field of
+    /// a configuration to its drain, thus the handles of all (nested)
+    /// configurations can be retrieved from the context after building them.
+    pub fn handle(&self, name: &str) -> LevelHandle {
+        self.lock_handles()
+            .entry(name.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns all level handles of this context by their name.
+    pub fn handles(&self) -> BTreeMap<String, LevelHandle> {
+        self.lock_handles().clone()
+    }
+
+    fn lock_handles(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, LevelHandle>> {
+        self.handles.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_outputs(&self) -> std::sync::MutexGuard<'_, Outputs> {
+        self.outputs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Builds the drain of a nested configuration within this context.
+    pub fn build(&self, cfg: &Config) -> Result<BoxDrain, Error> {
+        self.drains.build_in(cfg, self)
+    }
+}
+
+impl Default for Context<'static> {
+    /// Returns a context building nested drains via the default drain
+    /// factories (see [`drains()`](::drains)).
+    fn default() -> Self {
+        Context::new(drains())
     }
 }
 
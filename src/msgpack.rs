@@ -0,0 +1,372 @@
+//! Minimal MessagePack encoding and decoding.
+
+use std::io::{self, Read};
+
+
+/// The maximum nesting depth of arrays and maps accepted when reading.
+const MAX_DEPTH: usize = 128;
+
+/// Appends an unsigned integer in its shortest representation.
+pub fn write_uint(buf: &mut Vec<u8>, value: u64) {
+    if value < 0x80 {
+        buf.push(value as u8);
+    } else if value <= 0xff {
+        buf.push(0xcc);
+        buf.push(value as u8);
+    } else if value <= 0xffff {
+        buf.push(0xcd);
+        buf.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= 0xffff_ffff {
+        buf.push(0xce);
+        buf.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        buf.push(0xcf);
+        buf.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Appends a signed integer in its shortest representation.
+#[cfg(feature = "binary")]
+pub fn write_int(buf: &mut Vec<u8>, value: i64) {
+    if value >= 0 {
+        write_uint(buf, value as u64);
+    } else if value >= -32 {
+        buf.push(value as u8);
+    } else if value >= i64::from(i8::MIN) {
+        buf.push(0xd0);
+        buf.push(value as u8);
+    } else if value >= i64::from(i16::MIN) {
+        buf.push(0xd1);
+        buf.extend_from_slice(&(value as i16).to_be_bytes());
+    } else if value >= i64::from(i32::MIN) {
+        buf.push(0xd2);
+        buf.extend_from_slice(&(value as i32).to_be_bytes());
+    } else {
+        buf.push(0xd3);
+        buf.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Appends a double-precision float.
+#[cfg(feature = "binary")]
+pub fn write_f64(buf: &mut Vec<u8>, value: f64) {
+    buf.push(0xcb);
+    buf.extend_from_slice(&value.to_bits().to_be_bytes());
+}
+
+/// Appends a boolean.
+#[cfg(feature = "binary")]
+pub fn write_bool(buf: &mut Vec<u8>, value: bool) {
+    buf.push(if value { 0xc3 } else { 0xc2 });
+}
+
+/// Appends `nil`.
+#[cfg(feature = "binary")]
+pub fn write_nil(buf: &mut Vec<u8>) {
+    buf.push(0xc0);
+}
+
+/// Appends a string.
+pub fn write_str(buf: &mut Vec<u8>, value: &str) {
+    let len = value.len();
+
+    if len < 32 {
+        buf.push(0xa0 | len as u8);
+    } else if len <= 0xff {
+        buf.push(0xd9);
+        buf.push(len as u8);
+    } else if len <= 0xffff {
+        buf.push(0xda);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xdb);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Appends a binary blob.
+#[cfg(any(feature = "fluent", feature = "binary"))]
+pub fn write_bin(buf: &mut Vec<u8>, value: &[u8]) {
+    let len = value.len();
+
+    if len <= 0xff {
+        buf.push(0xc4);
+        buf.push(len as u8);
+    } else if len <= 0xffff {
+        buf.push(0xc5);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xc6);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+
+    buf.extend_from_slice(value);
+}
+
+/// Appends the header of an array with the specified number of elements.
+pub fn write_array_len(buf: &mut Vec<u8>, len: usize) {
+    if len < 16 {
+        buf.push(0x90 | len as u8);
+    } else if len <= 0xffff {
+        buf.push(0xdc);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xdd);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+/// Appends the header of a map with the specified number of entries.
+pub fn write_map_len(buf: &mut Vec<u8>, len: usize) {
+    if len < 16 {
+        buf.push(0x80 | len as u8);
+    } else if len <= 0xffff {
+        buf.push(0xde);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xdf);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+/// Appends an extension value of the specified type.
+#[cfg(feature = "fluent")]
+pub fn write_ext(buf: &mut Vec<u8>, ty: i8, data: &[u8]) {
+    let len = data.len();
+
+    match len {
+        1 => buf.push(0xd4),
+        2 => buf.push(0xd5),
+        4 => buf.push(0xd6),
+        8 => buf.push(0xd7),
+        16 => buf.push(0xd8),
+        _ if len <= 0xff => {
+            buf.push(0xc7);
+            buf.push(len as u8);
+        },
+        _ if len <= 0xffff => {
+            buf.push(0xc8);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        _ => {
+            buf.push(0xc9);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        },
+    }
+
+    buf.push(ty as u8);
+    buf.extend_from_slice(data);
+}
+
+
+/// A decoded MessagePack value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    F32(f32),
+    F64(f64),
+    Str(String),
+    Bin(Vec<u8>),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Ext(i8, Vec<u8>),
+}
+
+impl Value {
+    /// Returns the string contained in this value, if it is a string.
+    #[cfg(feature = "fluent")]
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::Str(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value associated with the specified string key, if this
+    /// value is a map.
+    #[cfg(feature = "fluent")]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Map(ref entries) => entries
+                .iter()
+                .find(|&(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a single value.
+///
+/// Values nested deeper than 128 arrays or maps are rejected as invalid data.
+pub fn read<R: Read>(r: &mut R) -> io::Result<Value> {
+    read_1(r, MAX_DEPTH)
+}
+
+fn read_1<R: Read>(r: &mut R, depth: usize) -> io::Result<Value> {
+    let marker = read_u8(r)?;
+
+    let value = match marker {
+        0x00..=0x7f => Value::Uint(u64::from(marker)),
+        0x80..=0x8f => read_map(r, depth, usize::from(marker & 0x0f))?,
+        0x90..=0x9f => read_array(r, depth, usize::from(marker & 0x0f))?,
+        0xa0..=0xbf => read_string(r, usize::from(marker & 0x1f))?,
+        0xc0 => Value::Nil,
+        0xc2 => Value::Bool(false),
+        0xc3 => Value::Bool(true),
+        0xc4 => {
+            let len = read_u8(r)?;
+            Value::Bin(read_bytes(r, usize::from(len))?)
+        },
+        0xc5 => {
+            let len = read_u16(r)?;
+            Value::Bin(read_bytes(r, usize::from(len))?)
+        },
+        0xc6 => {
+            let len = read_u32(r)?;
+            Value::Bin(read_bytes(r, len as usize)?)
+        },
+        0xc7 => {
+            let len = read_u8(r)?;
+            read_ext(r, usize::from(len))?
+        },
+        0xc8 => {
+            let len = read_u16(r)?;
+            read_ext(r, usize::from(len))?
+        },
+        0xc9 => {
+            let len = read_u32(r)?;
+            read_ext(r, len as usize)?
+        },
+        0xca => Value::F32(f32::from_bits(read_u32(r)?)),
+        0xcb => Value::F64(f64::from_bits(read_u64(r)?)),
+        0xcc => Value::Uint(u64::from(read_u8(r)?)),
+        0xcd => Value::Uint(u64::from(read_u16(r)?)),
+        0xce => Value::Uint(u64::from(read_u32(r)?)),
+        0xcf => Value::Uint(read_u64(r)?),
+        0xd0 => Value::Int(i64::from(read_u8(r)? as i8)),
+        0xd1 => Value::Int(i64::from(read_u16(r)? as i16)),
+        0xd2 => Value::Int(i64::from(read_u32(r)? as i32)),
+        0xd3 => Value::Int(read_u64(r)? as i64),
+        0xd4 => read_ext(r, 1)?,
+        0xd5 => read_ext(r, 2)?,
+        0xd6 => read_ext(r, 4)?,
+        0xd7 => read_ext(r, 8)?,
+        0xd8 => read_ext(r, 16)?,
+        0xd9 => {
+            let len = read_u8(r)?;
+            read_string(r, usize::from(len))?
+        },
+        0xda => {
+            let len = read_u16(r)?;
+            read_string(r, usize::from(len))?
+        },
+        0xdb => {
+            let len = read_u32(r)?;
+            read_string(r, len as usize)?
+        },
+        0xdc => {
+            let len = read_u16(r)?;
+            read_array(r, depth, usize::from(len))?
+        },
+        0xdd => {
+            let len = read_u32(r)?;
+            read_array(r, depth, len as usize)?
+        },
+        0xde => {
+            let len = read_u16(r)?;
+            read_map(r, depth, usize::from(len))?
+        },
+        0xdf => {
+            let len = read_u32(r)?;
+            read_map(r, depth, len as usize)?
+        },
+        0xe0..=0xff => Value::Int(i64::from(marker as i8)),
+        _ => return Err(invalid("invalid messagepack marker")),
+    };
+
+    Ok(value)
+}
+
+fn read_map<R: Read>(r: &mut R, depth: usize, len: usize) -> io::Result<Value> {
+    let depth = nested(depth)?;
+    let mut entries = Vec::with_capacity(len.min(1024));
+
+    for _ in 0..len {
+        let key = read_1(r, depth)?;
+        let value = read_1(r, depth)?;
+        entries.push((key, value));
+    }
+
+    Ok(Value::Map(entries))
+}
+
+fn read_array<R: Read>(r: &mut R, depth: usize, len: usize) -> io::Result<Value> {
+    let depth = nested(depth)?;
+    let mut values = Vec::with_capacity(len.min(1024));
+
+    for _ in 0..len {
+        values.push(read_1(r, depth)?);
+    }
+
+    Ok(Value::Array(values))
+}
+
+fn nested(depth: usize) -> io::Result<usize> {
+    depth.checked_sub(1).ok_or_else(|| invalid("messagepack value nested too deeply"))
+}
+
+fn read_string<R: Read>(r: &mut R, len: usize) -> io::Result<Value> {
+    String::from_utf8(read_bytes(r, len)?)
+        .map(Value::Str)
+        .map_err(|_| invalid("invalid utf-8 in messagepack string"))
+}
+
+fn read_ext<R: Read>(r: &mut R, len: usize) -> io::Result<Value> {
+    let ty = read_u8(r)? as i8;
+    Ok(Value::Ext(ty, read_bytes(r, len)?))
+}
+
+fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.take(len as u64).read_to_end(&mut buf)?;
+
+    if buf.len() == len {
+        Ok(buf)
+    } else {
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated messagepack value"))
+    }
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,197 @@
+//! Connection handling shared by the network drains.
+
+use std;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+
+/// The delay before the first reconnection attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The maximum time to wait for a TCP connection to be established.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum time to wait for a write to a stream to complete. A write
+/// timing out is handled like a lost connection.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// Schedules connection attempts, doubling the delay between two attempts
+/// after each failure up to a maximum.
+#[derive(Debug)]
+pub struct Backoff {
+    max:      Duration,
+    delay:    Duration,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    /// Creates a new schedule waiting at most `max` between two attempts. The
+    /// first attempt is due immediately.
+    pub fn new(max: Duration) -> Self {
+        Backoff {
+            max,
+            delay: INITIAL_BACKOFF.min(max),
+            retry_at: None,
+        }
+    }
+
+    /// Returns `true` if the next attempt is due.
+    pub fn is_due(&self) -> bool {
+        self.retry_at.map_or(true, |retry_at| Instant::now() >= retry_at)
+    }
+
+    /// Schedules the next attempt after a failure and increases the delay.
+    pub fn failed(&mut self) {
+        self.retry_at = Some(Instant::now() + self.delay);
+        self.delay = std::cmp::min(self.delay * 2, self.max);
+    }
+
+    /// Resets the delay after a successful attempt.
+    pub fn succeeded(&mut self) {
+        self.retry_at = None;
+        self.delay = INITIAL_BACKOFF.min(self.max);
+    }
+}
+
+
+/// A transport to an endpoint which can be re-established after a failure.
+#[cfg(any(feature = "syslog", feature = "gelf", feature = "fluent"))]
+pub trait Reconnect {
+    /// Re-establishes the connection, e.g. after the endpoint has restarted.
+    fn reconnect(&mut self) -> io::Result<()>;
+}
+
+
+/// A connection via a transport, re-established with an increasing delay
+/// after a failure. Messages which could not be sent are counted, so that the
+/// network drains can report them once sending succeeds again.
+#[cfg(any(feature = "syslog", feature = "gelf", feature = "fluent"))]
+pub struct Connection<T> {
+    transport: T,
+    backoff:   Backoff,
+    broken:    bool,
+    dropped:   u64,
+    error:     String,
+}
+
+#[cfg(any(feature = "syslog", feature = "gelf", feature = "fluent"))]
+impl<T: Reconnect> Connection<T> {
+    /// Creates a new connection via the specified transport, waiting at most
+    /// `backoff` between two connection attempts.
+    ///
+    /// The transport does not have to be connected yet, it is reconnected
+    /// once sending via it fails.
+    pub fn new(transport: T, backoff: Duration) -> Self {
+        Connection {
+            transport,
+            backoff: Backoff::new(backoff),
+            broken: false,
+            dropped: 0,
+            error: String::new(),
+        }
+    }
+
+    /// Returns the transport of this connection.
+    #[cfg(feature = "gelf")]
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Sends a message via `send`, reconnecting if the connection failed and
+    /// the next attempt is due.
+    pub fn send<F>(&mut self, mut send: F) -> io::Result<()>
+    where
+        F: FnMut(&mut T) -> io::Result<()>,
+    {
+        if !self.broken {
+            match send(&mut self.transport) {
+                Ok(()) => return Ok(()),
+                Err(e) => self.error = e.to_string(),
+            }
+
+            self.broken = true;
+        }
+
+        // report the last failure while waiting
+        if !self.backoff.is_due() {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, self.error.clone()));
+        }
+
+        let result = self.transport.reconnect().and_then(|_| send(&mut self.transport));
+
+        match result {
+            Ok(()) => {
+                self.broken = false;
+                self.backoff.succeeded();
+            },
+            Err(ref e) => {
+                self.error = e.to_string();
+                self.backoff.failed();
+            },
+        }
+
+        result
+    }
+
+    /// Counts the specified number of messages as dropped for the given
+    /// reason.
+    pub fn discard(&mut self, count: u64, error: &io::Error) {
+        self.dropped += count;
+        self.error = error.to_string();
+    }
+
+    /// Sends a report of the messages dropped since the last report, if any,
+    /// via `send`, which is passed their number and the reason for dropping
+    /// them.
+    pub fn report<F>(&mut self, mut send: F)
+    where
+        F: FnMut(&mut T, u64, &str) -> io::Result<()>,
+    {
+        if self.dropped == 0 {
+            return;
+        }
+
+        let (count, error) = (self.dropped, self.error.clone());
+
+        if self.send(|transport| send(transport, count, &error)).is_ok() {
+            self.dropped = 0;
+        }
+    }
+}
+
+
+/// Opens a TCP connection to the specified `host:port` address, waiting at
+/// most `timeout` for each resolved address. Writes to the returned stream
+/// time out after `WRITE_TIMEOUT`.
+pub fn connect_tcp(addr: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut error = None;
+
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                return Ok(stream);
+            },
+            Err(e) => error = Some(e),
+        }
+    }
+
+    Err(error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "address did not resolve")
+    }))
+}
+
+/// Opens a connection to the unix stream socket at `path`. Writes to the
+/// returned stream time out after `WRITE_TIMEOUT`.
+#[cfg(unix)]
+pub fn connect_unix(path: &Path) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    Ok(stream)
+}
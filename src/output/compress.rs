@@ -0,0 +1,326 @@
+//! Streaming compression of log files.
+
+use common::Compression;
+use output::Reopenable;
+
+use std;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+
+/// The default maximum time between two flush points.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of uncompressed bytes between two flush points.
+const FLUSH_SIZE: u64 = 4 * 1024 * 1024;
+
+
+/// A writer compressing its output as a sequence of independent gzip members
+/// or zstd frames.
+///
+/// Written data is compressed into memory. At flush points, the current
+/// member is finished and written to the file as a whole, thus the file only
+/// ever contains complete members and remains decodable (e.g. via `zcat` or
+/// `zstdcat`) if the process crashes. Flush points are only placed at record
+/// boundaries, i.e. when the writer is flushed, once the configured interval
+/// has passed since the first record of the current member or the member has
+/// grown large. Flush points can also be placed via [`tick`](Compressed::tick)
+/// without further records being written, which the default factories do
+/// from a background thread. The last member is written when the writer is
+/// dropped, ignoring any error. Call [`finish`](Compressed::finish) before
+/// to handle errors writing it.
+///
+/// Rotation and reopening of the underlying file happen at flush points, so
+/// that each file is a valid compressed stream on its own. An optional header
+/// is written as separate member to each empty file.
+#[derive(Debug)]
+pub struct Compressed<W: Reopenable> {
+    inner:       W,
+    compression: Compression,
+    header:      Vec<u8>,
+    interval:    Duration,
+    encoder:     Option<Encoder>,
+    started:     Instant,
+    input:       u64,
+    boundary:    bool,
+}
+
+impl<W: Reopenable> Compressed<W> {
+    /// Wraps the specified writer, compressing all data written to it.
+    pub fn new(inner: W, compression: Compression) -> Self {
+        Compressed {
+            inner,
+            compression,
+            header: Vec::new(),
+            interval: FLUSH_INTERVAL,
+            encoder: None,
+            started: Instant::now(),
+            input: 0,
+            boundary: true,
+        }
+    }
+
+    /// Writes the specified header to each empty file before its first member.
+    pub fn with_header(mut self, header: Vec<u8>) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Sets the maximum time between two flush points (default: 1s).
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the maximum time between two flush points.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Finishes the current member and writes it to the underlying file.
+    ///
+    /// Does nothing if no data has been written since the last flush point.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let data = match self.encoder.take() {
+            Some(encoder) => encoder.finish()?,
+            None => return Ok(()),
+        };
+
+        self.input = 0;
+        self.inner.prepare()?;
+
+        if !self.header.is_empty() && self.inner.file().metadata()?.len() == 0 {
+            let mut encoder = Encoder::new(self.compression)?;
+            encoder.write_all(&self.header)?;
+            self.inner.write_all(&encoder.finish()?)?;
+        }
+
+        self.inner.write_all(&data)?;
+        self.inner.flush()
+    }
+
+    /// Places a flush point if the writer is at a record boundary and the
+    /// interval has passed since the first record of the current member.
+    ///
+    /// This allows the current member to be written while no further records
+    /// are being logged.
+    pub fn tick(&mut self) -> io::Result<()> {
+        if self.boundary && self.is_due() {
+            self.finish()?;
+        }
+
+        Ok(())
+    }
+
+    fn is_due(&self) -> bool {
+        self.encoder.is_some()
+            && (self.started.elapsed() >= self.interval || self.input >= FLUSH_SIZE)
+    }
+}
+
+impl<W: Reopenable> Reopenable for Compressed<W> {
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn file(&self) -> &File {
+        self.inner.file()
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.finish()?;
+        self.inner.reopen()
+    }
+}
+
+impl<W: Reopenable> Write for Compressed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.boundary = false;
+
+        if self.encoder.is_none() {
+            self.encoder = Some(Encoder::new(self.compression)?);
+            self.started = Instant::now();
+        }
+
+        let n = match self.encoder {
+            Some(ref mut encoder) => encoder.write(buf)?,
+            None => 0,
+        };
+
+        self.input += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.boundary = true;
+
+        if self.is_due() {
+            self.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Reopenable> Drop for Compressed<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+
+
+/// A writer shared with a background thread periodically ticking it, e.g. to
+/// place the flush points of a [`Compressed`](Compressed) writer.
+///
+/// Errors of the background thread are returned by the next flush. Dropping
+/// this value stops the thread and waits for it to exit.
+pub(crate) struct Ticking<W> {
+    shared: Arc<Mutex<Ticked<W>>>,
+    sender: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Ticked<W> {
+    writer: W,
+    error:  Option<io::Error>,
+}
+
+impl<W: Write + Send + 'static> Ticking<W> {
+    /// Spawns a new thread passing the specified writer to `tick` each time
+    /// the given interval has passed.
+    pub fn spawn<F>(writer: W, interval: Duration, tick: F) -> io::Result<Self>
+    where
+        F: Fn(&mut W) -> io::Result<()> + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Ticked { writer, error: None }));
+        let (sender, receiver) = channel::<()>();
+
+        let ticked = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("slog-conf-compress".into())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(interval) {
+                    let mut ticked = ticked.lock().unwrap_or_else(|e| e.into_inner());
+
+                    if let Err(e) = tick(&mut ticked.writer) {
+                        ticked.error = Some(e);
+                    }
+                }
+            })?;
+
+        Ok(Ticking {
+            shared,
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ticked<W>> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send + 'static> Write for Ticking<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().writer.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.lock().writer.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut ticked = self.lock();
+        let result = ticked.writer.flush();
+
+        match ticked.error.take() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+impl<W> Drop for Ticking<W> {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+/// An in-memory encoder for a single member.
+enum Encoder {
+    #[cfg(feature = "gzip")]
+    Gzip(::flate2::write::GzEncoder<Vec<u8>>),
+
+    #[cfg(feature = "zstd")]
+    Zstd(::zstd::stream::Encoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(compression: Compression) -> io::Result<Self> {
+        match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let level = ::flate2::Compression::default();
+                Ok(Encoder::Gzip(::flate2::write::GzEncoder::new(Vec::new(), level)))
+            },
+
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Encoder::Zstd(::zstd::stream::Encoder::new(Vec::new(), 0)?)),
+        }
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish(),
+
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Write for Encoder {
+    #[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(ref mut encoder) => encoder.write(buf),
+
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(ref mut encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Encoder")
+    }
+}
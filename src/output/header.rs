@@ -0,0 +1,87 @@
+//! Headers written at the start of each log file.
+
+use output::Reopenable;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+
+/// A writer writing a header (e.g. the header row of a CSV file) whenever a
+/// record is written to an empty file.
+///
+/// This covers newly created and truncated files as well as files started by
+/// rotation or reopening, whereas appending to a non-empty file does not
+/// repeat the header. The file is only checked at record boundaries, i.e.
+/// directly after the writer has been flushed.
+#[derive(Debug)]
+pub struct WithHeader<W> {
+    inner:    W,
+    header:   Vec<u8>,
+    boundary: bool,
+}
+
+impl<W: Reopenable> WithHeader<W> {
+    /// Wraps the specified writer, writing the given header to empty files.
+    pub fn new(inner: W, header: Vec<u8>) -> Self {
+        WithHeader {
+            inner,
+            header,
+            boundary: true,
+        }
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Reopenable> Reopenable for WithHeader<W> {
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn file(&self) -> &File {
+        self.inner.file()
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.inner.reopen()
+    }
+
+    fn prepare(&mut self) -> io::Result<()> {
+        if self.boundary {
+            self.boundary = false;
+            self.inner.prepare()?;
+
+            if self.inner.file().metadata()?.len() == 0 {
+                self.inner.write_all(&self.header)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Reopenable> Write for WithHeader<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.prepare()?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.boundary = true;
+        self.inner.flush()
+    }
+}
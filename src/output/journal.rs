@@ -0,0 +1,154 @@
+//! Priority prefixes for output captured by systemd-journald.
+
+use common::{Journal, Level};
+
+use std;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+/// A shared syslog severity, set per record by a
+/// [`Prioritize`](::drain::Prioritize) drain and read by a
+/// [`PriorityPrefix`](PriorityPrefix) writer.
+#[derive(Debug, Clone)]
+pub struct Priority(Arc<AtomicUsize>);
+
+impl Priority {
+    /// Creates a new priority with severity `info`.
+    pub fn new() -> Self {
+        Priority(Arc::new(AtomicUsize::new(Level::Info.severity() as usize)))
+    }
+
+    /// Sets the severity corresponding to the specified level.
+    pub fn set(&self, level: Level) {
+        self.0.store(level.severity() as usize, Ordering::SeqCst);
+    }
+
+    /// Returns the current severity.
+    pub fn get(&self) -> u8 {
+        self.0.load(Ordering::SeqCst) as u8
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::new()
+    }
+}
+
+
+/// A writer prefixing each line with the `sd-daemon` priority prefix (e.g.
+/// `<4>`) of the current [`Priority`](Priority).
+#[derive(Debug)]
+pub struct PriorityPrefix<W> {
+    inner:      W,
+    priority:   Priority,
+    line_start: bool,
+}
+
+impl<W: Write> PriorityPrefix<W> {
+    /// Creates a new writer prefixing the lines written to `inner`.
+    pub fn new(inner: W, priority: Priority) -> Self {
+        PriorityPrefix {
+            inner,
+            priority,
+            line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for PriorityPrefix<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+
+        while !rest.is_empty() {
+            if self.line_start {
+                write!(self.inner, "<{}>", self.priority.get())?;
+                self.line_start = false;
+            }
+
+            let len = match rest.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    self.line_start = true;
+                    pos + 1
+                },
+                None => rest.len(),
+            };
+
+            self.inner.write_all(&rest[..len])?;
+            rest = &rest[len..];
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+
+/// The standard streams that may be connected to the journal.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stream {
+    /// The standard output stream.
+    Stdout,
+
+    /// The standard error stream.
+    Stderr,
+}
+
+/// Returns `true` if lines should be prefixed according to the specified
+/// setting, given the standard stream written to (if any).
+pub fn is_enabled(journal: Journal, stream: Option<Stream>) -> bool {
+    match journal {
+        Journal::Auto => stream.map_or(false, is_journal_stream),
+        Journal::Always => true,
+        Journal::Never => false,
+    }
+}
+
+/// Returns `true` if the specified stream is connected to the journal.
+///
+/// This is the case if the device and inode number of the stream match the
+/// ones given in the `JOURNAL_STREAM` environment variable.
+#[cfg(unix)]
+pub fn is_journal_stream(stream: Stream) -> bool {
+    use std::fs::File;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::FromRawFd;
+
+    let var = match std::env::var("JOURNAL_STREAM") {
+        Ok(var) => var,
+        Err(_) => return false,
+    };
+
+    let mut parts = var.splitn(2, ':').map(|p| p.parse::<u64>().ok());
+    let (dev, ino) = match (parts.next(), parts.next()) {
+        (Some(Some(dev)), Some(Some(ino))) => (dev, ino),
+        _ => return false,
+    };
+
+    let fd = match stream {
+        Stream::Stdout => 1,
+        Stream::Stderr => 2,
+    };
+
+    // the descriptor is owned by the standard stream, it must not be closed
+    let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+
+    match file.metadata() {
+        Ok(meta) => meta.dev() == dev && meta.ino() == ino,
+        Err(_) => false,
+    }
+}
+
+/// Returns `true` if the specified stream is connected to the journal.
+///
+/// Always `false` on non-unix platforms.
+#[cfg(not(unix))]
+pub fn is_journal_stream(_stream: Stream) -> bool {
+    false
+}
@@ -0,0 +1,322 @@
+//! Writers for the output-targets of the default factories.
+//!
+//! The default factories use [`open`](open) (via
+//! [`Context::open`](::Context::open)) to create a writer for a
+//! [`Target`](::common::Target). The writers provided by this module can also
+//! be used to implement custom factories.
+
+mod compress;
+mod header;
+pub mod journal;
+mod network;
+mod reopen;
+mod retention;
+mod ring;
+mod rotate;
+
+pub use self::compress::Compressed;
+pub use self::header::WithHeader;
+pub use self::network::{Dropped, NetworkWriter};
+pub use self::reopen::{LogFile, Reopenable, Reopening};
+pub use self::ring::{read_ring, RingFile};
+pub use self::rotate::RotatingFile;
+
+use Error;
+use common::{Compression, OpenMode, Reopen, Target};
+use self::compress::Ticking;
+
+use std;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+
+/// Opens a writer for the specified target.
+///
+/// Files will be created (including their parent directories) according to
+/// the `OpenMode` of the target.
+pub fn open(target: &Target) -> Result<Box<Write + Send>, Error> {
+    open_1(target, None).map(|(output, _)| output)
+}
+
+/// Opens a writer for the specified target, writing the given header at the
+/// start of the output.
+///
+/// Files are wrapped in a [`WithHeader`](WithHeader) writer, i.e. the header
+/// is written to each empty file before its first record, but not to existing
+/// files that are appended to. Compressed files write the header in the same
+/// way (see [`Compressed`](Compressed)). Ring files never contain the header,
+/// as it would be overwritten once the file wraps around. For all other
+/// targets, the header is written immediately.
+pub fn open_with_header(target: &Target, header: &[u8]) -> Result<Box<Write + Send>, Error> {
+    open_1(target, Some(header)).map(|(output, _)| output)
+}
+
+fn open_1(target: &Target, header: Option<&[u8]>) -> Result<(Box<Write + Send>, Dropped), Error> {
+    let mut dropped = Dropped::default();
+
+    let mut output: Box<Write + Send> = match *target {
+        Target::Stdout => Box::new(std::io::stdout()),
+        Target::Stderr => Box::new(std::io::stderr()),
+        Target::File {
+            ref path,
+            mode,
+            ref rotate,
+            ref retention,
+            ref reopen,
+            compression,
+        } => {
+            let compress = retention.as_ref().map_or(false, |r| r.compress.is_some());
+            if compression.is_some() && compress {
+                return Err(Error::msg(
+                    "a compressed file cannot be compressed again after rotation",
+                ));
+            }
+
+            let output = match *rotate {
+                Some(ref rotate) => {
+                    let mut file = RotatingFile::open(path, mode, rotate.clone())?;
+
+                    if let Some(ref retention) = *retention {
+                        file = file.with_retention(retention.clone())?;
+                    }
+
+                    headed(file, header, compression, reopen)?
+                },
+                None if retention.is_some() => {
+                    return Err(Error::msg("a retention policy requires file rotation"));
+                },
+                None => headed(LogFile::open(path, mode)?, header, compression, reopen)?,
+            };
+
+            return Ok((output, dropped));
+        },
+        Target::Network {
+            ref connect,
+            buffer,
+            backoff,
+        } => {
+            let writer = NetworkWriter::new(connect.clone(), buffer, backoff.into());
+            dropped = writer.unreported();
+            Box::new(writer)
+        },
+        Target::Ring { ref path, size } => {
+            // a header would be overwritten once the file wraps around
+            return Ok((Box::new(RingFile::open(path, size.bytes())?), dropped));
+        },
+    };
+
+    if let Some(header) = header {
+        output.write_all(header)?;
+        output.flush()?;
+    }
+
+    Ok((output, dropped))
+}
+
+/// The outputs opened while building a configuration.
+///
+/// The writers for file, ring, and network targets opened via
+/// [`Context::open`](::Context::open) are recorded in the context. When the
+/// configuration is rebuilt (e.g. on reload) with these outputs (see
+/// [`Context::with_outputs`](::Context::with_outputs)), each target which is
+/// configured identically to a previous one continues to use the writer of
+/// the previous drain instead of opening the target again. Thus, files are not
+/// truncated or re-created and rotated files keep a single writer.
+#[derive(Clone, Default)]
+pub struct Outputs {
+    entries: Vec<Output>,
+}
+
+#[derive(Clone)]
+struct Output {
+    target:  Target,
+    header:  Option<Vec<u8>>,
+    writer:  Shared,
+    dropped: Dropped,
+}
+
+impl Outputs {
+    /// Creates a new, empty set of outputs.
+    pub fn new() -> Self {
+        Outputs::default()
+    }
+
+    /// Returns the number of recorded outputs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no output has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Opens a writer for the specified target and records it, reusing the
+    /// writer of an identical output from `previous` if there is one.
+    ///
+    /// Terminal outputs are neither recorded nor reused.
+    pub(crate) fn open(
+        &mut self,
+        previous: &Outputs,
+        target: &Target,
+        header: Option<&[u8]>,
+    ) -> Result<Opened, Error> {
+        if let Target::Stdout | Target::Stderr = *target {
+            let (writer, dropped) = open_1(target, header)?;
+            return Ok(Opened { writer, dropped });
+        }
+
+        let reused = previous
+            .entries
+            .iter()
+            .find(|o| o.target == *target && o.header.as_deref() == header)
+            .map(|o| (o.writer.clone(), o.dropped.clone()));
+
+        let (writer, dropped) = match reused {
+            Some(reused) => reused,
+            None => {
+                let (writer, dropped) = open_1(target, header)?;
+                (Shared(Arc::new(Mutex::new(writer))), dropped)
+            },
+        };
+
+        self.entries.push(Output {
+            target:  target.clone(),
+            header:  header.map(<[u8]>::to_vec),
+            writer:  writer.clone(),
+            dropped: dropped.clone(),
+        });
+
+        Ok(Opened {
+            writer: Box::new(writer),
+            dropped,
+        })
+    }
+}
+
+
+/// A writer opened via [`Context::open`](::Context::open).
+pub struct Opened {
+    writer:  Box<Write + Send>,
+    dropped: Dropped,
+}
+
+impl Opened {
+    /// Returns the handle to the number of records dropped by this output,
+    /// e.g. while a network endpoint is unavailable.
+    pub fn dropped(&self) -> Dropped {
+        self.dropped.clone()
+    }
+}
+
+impl Write for Opened {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.writer.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+
+/// A writer shared by the drains of subsequent builds.
+#[derive(Clone)]
+struct Shared(Arc<Mutex<Box<Write + Send>>>);
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Box<Write + Send>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.lock().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+
+fn headed<W>(
+    file: W,
+    header: Option<&[u8]>,
+    compression: Option<Compression>,
+    reopen: &Option<Reopen>,
+) -> Result<Box<Write + Send>, Error>
+where
+    W: Reopenable + Send + 'static,
+{
+    match (header, compression) {
+        (Some(header), Some(compression)) => {
+            let file = Compressed::new(file, compression).with_header(header.to_vec());
+            ticking(file, reopen)
+        },
+        (None, Some(compression)) => ticking(Compressed::new(file, compression), reopen),
+        (Some(header), None) => reopening(WithHeader::new(file, header.to_vec()), reopen),
+        (None, None) => reopening(file, reopen),
+    }
+}
+
+/// Places the flush points of the compressed file from a background thread,
+/// so that records are written even if no further records are logged.
+fn ticking<W>(file: Compressed<W>, reopen: &Option<Reopen>) -> Result<Box<Write + Send>, Error>
+where
+    W: Reopenable + Send + 'static,
+{
+    let interval = file.interval();
+
+    match *reopen {
+        Some(reopen) => {
+            let file = Reopening::new(file, reopen)?;
+            Ok(Box::new(Ticking::spawn(file, interval, |file| file.get_mut().tick())?))
+        },
+        None => Ok(Box::new(Ticking::spawn(file, interval, Compressed::tick)?)),
+    }
+}
+
+fn reopening<W>(file: W, reopen: &Option<Reopen>) -> Result<Box<Write + Send>, Error>
+where
+    W: Reopenable + Send + 'static,
+{
+    match *reopen {
+        Some(reopen) => Ok(Box::new(Reopening::new(file, reopen)?)),
+        None => Ok(Box::new(file)),
+    }
+}
+
+/// Opens the file at the specified path with the given `OpenMode`.
+///
+/// Creates all parent directories of the file if they do not exist.
+pub fn open_file<P>(path: P, mode: OpenMode) -> std::io::Result<File>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut opt = OpenOptions::new();
+
+    match mode {
+        OpenMode::Append => opt.create(true).write(true).append(true),
+        OpenMode::Truncate => opt.create(true).write(true).truncate(true),
+        OpenMode::New => opt.create_new(true).write(true),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    opt.open(path)
+}
@@ -0,0 +1,260 @@
+//! Newline-delimited output to TCP or unix stream sockets.
+
+use common::Endpoint;
+#[cfg(unix)]
+use net::connect_unix;
+use net::{connect_tcp, Backoff, CONNECT_TIMEOUT};
+
+use std;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+
+/// A writer sending complete lines to a network endpoint.
+///
+/// Output is split into lines, each of which is considered a single record.
+/// If the connection is lost or cannot be established, records are buffered
+/// up to the configured limit, dropping the oldest records once it is
+/// exceeded. A record which has only been sent partially when the connection
+/// is lost is dropped as well, as it cannot be continued on a new connection.
+/// A write which does not complete within five seconds is handled like a lost
+/// connection. A new connection is attempted on subsequent writes, with the
+/// delay between attempts doubling up to the configured maximum backoff. Once
+/// connected again, buffered records are sent and the number of dropped
+/// records is passed on to the [`Dropped`](Dropped) handle of the writer, via
+/// which the default factories report them as warning record.
+///
+/// Writing to this writer never fails.
+pub struct NetworkWriter {
+    endpoint:   Endpoint,
+    capacity:   usize,
+    backoff:    Backoff,
+    stream:     Option<Box<Write + Send>>,
+    partial:    Vec<u8>,
+    queue:      VecDeque<Vec<u8>>,
+    dropped:    u64,
+    error:      String,
+    unreported: Dropped,
+}
+
+impl NetworkWriter {
+    /// Creates a new writer for the specified endpoint, buffering at most
+    /// `capacity` records while disconnected and waiting at most `backoff`
+    /// between two connection attempts.
+    ///
+    /// A connection is attempted immediately, failure to connect is not an
+    /// error.
+    pub fn new(endpoint: Endpoint, capacity: usize, backoff: Duration) -> Self {
+        let mut writer = NetworkWriter {
+            endpoint,
+            capacity,
+            backoff: Backoff::new(backoff),
+            stream: None,
+            partial: Vec::new(),
+            queue: VecDeque::new(),
+            dropped: 0,
+            error: String::new(),
+            unreported: Dropped::default(),
+        };
+
+        writer.reconnect();
+        writer
+    }
+
+    /// Returns the endpoint this writer sends records to.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Returns the number of records dropped since the last connection was
+    /// established.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the handle to which the number of dropped records is passed
+    /// once a connection has been established again.
+    pub fn unreported(&self) -> Dropped {
+        self.unreported.clone()
+    }
+
+    /// Attempts to (re-)connect if the backoff delay has passed, returning
+    /// `true` if connected.
+    fn reconnect(&mut self) -> bool {
+        if self.stream.is_some() {
+            return true;
+        }
+
+        if !self.backoff.is_due() {
+            return false;
+        }
+
+        match connect(&self.endpoint) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.backoff.succeeded();
+
+                if self.dropped > 0 {
+                    self.unreported.add(self.dropped, &self.error);
+                    self.dropped = 0;
+                }
+
+                true
+            },
+            Err(e) => {
+                self.error = e.to_string();
+                self.backoff.failed();
+                false
+            },
+        }
+    }
+
+    fn disconnect(&mut self, error: &io::Error) {
+        self.stream = None;
+        self.error = error.to_string();
+        self.backoff.failed();
+    }
+
+    /// Sends all buffered records, returning `true` if the buffer is empty.
+    fn drain_queue(&mut self) -> bool {
+        while let Some(line) = self.queue.pop_front() {
+            if !self.send(&line) {
+                self.queue.push_front(line);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Sends a single record on the current connection, disconnecting on
+    /// failure. Returns `false` if nothing has been sent, i.e. if the record
+    /// should be sent again later. A partially sent record is dropped.
+    fn send(&mut self, line: &[u8]) -> bool {
+        let result = match self.stream {
+            Some(ref mut stream) => write_line(stream, line),
+            None => return false,
+        };
+
+        match result {
+            Ok(()) => true,
+            Err((written, e)) => {
+                self.disconnect(&e);
+
+                if written > 0 {
+                    self.dropped += 1;
+                }
+
+                written > 0
+            },
+        }
+    }
+
+    fn enqueue(&mut self, line: Vec<u8>) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+
+        if self.queue.len() >= self.capacity {
+            self.queue.pop_front();
+            self.dropped += 1;
+        }
+
+        self.queue.push_back(line);
+    }
+
+    fn deliver(&mut self, line: Vec<u8>) {
+        if self.reconnect() && self.drain_queue() && self.send(&line) {
+            return;
+        }
+
+        self.enqueue(line);
+    }
+}
+
+impl Write for NetworkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+
+        while let Some(pos) = self.partial.iter().position(|&b| b == b'\n') {
+            let rest = self.partial.split_off(pos + 1);
+            let line = std::mem::replace(&mut self.partial, rest);
+            self.deliver(line);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.queue.is_empty() && self.reconnect() {
+            self.drain_queue();
+        }
+
+        Ok(())
+    }
+}
+
+
+/// A handle to the number of records dropped by an output, which have not
+/// been reported yet.
+///
+/// Obtained via [`Context::open`](::Context::open), the default factories
+/// wrap their drain in a [`Reporting`](::drain::Reporting) drain to report
+/// dropped records as warning record.
+#[derive(Debug, Clone, Default)]
+pub struct Dropped(Arc<Mutex<Option<(u64, String)>>>);
+
+impl Dropped {
+    /// Adds the specified number of records, dropped for the given reason.
+    pub fn add(&self, count: u64, reason: &str) {
+        let mut dropped = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let total = dropped.as_ref().map_or(0, |d| d.0) + count;
+
+        *dropped = Some((total, reason.to_owned()));
+    }
+
+    /// Takes the number of unreported records and the reason the last one has
+    /// been dropped for, if any records have been dropped.
+    pub fn take(&self) -> Option<(u64, String)> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+
+/// Writes a complete line, returning the number of bytes written on failure.
+fn write_line(stream: &mut Write, line: &[u8]) -> Result<(), (usize, io::Error)> {
+    let mut written = 0;
+
+    while written < line.len() {
+        match stream.write(&line[written..]) {
+            Ok(0) => return Err((written, io::ErrorKind::WriteZero.into())),
+            Ok(n) => written += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err((written, e)),
+        }
+    }
+
+    stream.flush().map_err(|e| (written, e))
+}
+
+fn connect(endpoint: &Endpoint) -> io::Result<Box<Write + Send>> {
+    match *endpoint {
+        Endpoint::Tcp(ref addr) => {
+            let stream = connect_tcp(addr, CONNECT_TIMEOUT)?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        },
+        #[cfg(unix)]
+        Endpoint::Unix(ref path) => {
+            Ok(Box::new(connect_unix(path)?))
+        },
+        #[cfg(not(unix))]
+        Endpoint::Unix(_) => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "unix sockets are not supported on this platform",
+        )),
+    }
+}
@@ -0,0 +1,191 @@
+//! Reopening of log files on signals or after they have been moved.
+
+use Error;
+use common::{OpenMode, Reopen};
+use output::open_file;
+use signal::{Signal, Watch};
+
+use std;
+use std::fs::{File, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+
+/// A writer backed by a file that can be reopened.
+pub trait Reopenable: Write {
+    /// Returns the path of the file currently being written to.
+    fn path(&self) -> &Path;
+
+    /// Returns the file currently being written to.
+    fn file(&self) -> &File;
+
+    /// Reopens the file at its current path in append-mode.
+    fn reopen(&mut self) -> io::Result<()>;
+
+    /// Prepares the writer for the next record, e.g. by rotating the file.
+    ///
+    /// Called at record boundaries before any part of the record has been
+    /// written. The default implementation does nothing.
+    fn prepare(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// A plain log file that can be reopened.
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl LogFile {
+    /// Opens the file at the specified path with the given `OpenMode`.
+    ///
+    /// Creates all parent directories of the file if they do not exist.
+    pub fn open<P>(path: P, mode: OpenMode) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let file = open_file(&path, mode)?;
+
+        Ok(LogFile { path, file })
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Reopenable for LogFile {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = open_file(&self.path, OpenMode::Append)?;
+        Ok(())
+    }
+}
+
+
+/// A writer reopening its underlying file according to a
+/// [`Reopen`](::common::Reopen) policy.
+///
+/// The file is only reopened at record boundaries, i.e. directly after the
+/// writer has been flushed.
+#[derive(Debug)]
+pub struct Reopening<W> {
+    inner:    W,
+    signal:   Option<Watch>,
+    moved:    bool,
+    checked:  Instant,
+    boundary: bool,
+}
+
+impl<W: Reopenable> Reopening<W> {
+    /// Wraps the specified writer, reopening it according to the given policy.
+    ///
+    /// Returns an error if reopening on signals has been requested but is not
+    /// supported.
+    pub fn new(inner: W, reopen: Reopen) -> Result<Self, Error> {
+        let signal = if reopen.signal {
+            Some(Watch::new(Signal::Hup)?)
+        } else {
+            None
+        };
+
+        Ok(Reopening {
+            inner,
+            signal,
+            moved: reopen.moved,
+            checked: Instant::now(),
+            boundary: true,
+        })
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn is_due(&mut self) -> bool {
+        let signaled = self.signal.as_mut().map_or(false, |watch| watch.poll() > 0);
+
+        if signaled {
+            return true;
+        }
+
+        if self.moved && self.checked.elapsed() >= Duration::from_secs(1) {
+            self.checked = Instant::now();
+            return self.is_moved();
+        }
+
+        false
+    }
+
+    fn is_moved(&self) -> bool {
+        let current = match std::fs::metadata(self.inner.path()) {
+            Ok(meta) => meta,
+            Err(_) => return true,
+        };
+
+        match self.inner.file().metadata() {
+            Ok(open) => !is_same_file(&current, &open),
+            Err(_) => false,
+        }
+    }
+}
+
+impl<W: Reopenable> Write for Reopening<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.boundary {
+            self.boundary = false;
+
+            if self.is_due() {
+                self.inner.reopen()?;
+            }
+        }
+
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.boundary = true;
+        self.inner.flush()
+    }
+}
+
+
+#[cfg(unix)]
+fn is_same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
@@ -0,0 +1,242 @@
+//! Background enforcement of retention policies for rotated files.
+
+use common::{Compression, Retention, Suffix};
+use output::rotate::{scan, Rolled};
+
+use std;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, Sender};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+
+/// A background thread applying a `Retention` policy to the files of a
+/// `RotatingFile`.
+///
+/// The policy is applied once on creation and then each time the thread is
+/// notified. Errors of the background thread are kept until they are taken
+/// via [`take_error`](Retainer::take_error). Dropping this value waits for
+/// pending work to complete.
+#[derive(Debug)]
+pub(crate) struct Retainer {
+    current: Arc<Mutex<PathBuf>>,
+    error:   Arc<Mutex<Option<io::Error>>>,
+    sender:  Option<Sender<()>>,
+    thread:  Option<JoinHandle<()>>,
+}
+
+impl Retainer {
+    /// Spawns a new thread applying the specified policy to the files with
+    /// the given base path and suffix.
+    pub fn spawn(path: PathBuf, suffix: Suffix, current: PathBuf, retention: Retention)
+        -> io::Result<Self>
+    {
+        let current = Arc::new(Mutex::new(current));
+        let error = Arc::new(Mutex::new(None));
+        let (sender, receiver) = channel();
+
+        let worker = Worker {
+            path,
+            suffix,
+            retention,
+            current: current.clone(),
+            error: error.clone(),
+        };
+
+        let thread = std::thread::Builder::new()
+            .name("slog-conf-retention".into())
+            .spawn(move || {
+                worker.apply();
+
+                while receiver.recv().is_ok() {
+                    // coalesce pending notifications
+                    while receiver.try_recv().is_ok() {}
+                    worker.apply();
+                }
+            })?;
+
+        Ok(Retainer {
+            current,
+            error,
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Locks the path of the current file.
+    ///
+    /// The lock must be held while a new file is being created and until the
+    /// path of the current file has been updated.
+    pub fn lock(&self) -> MutexGuard<'_, PathBuf> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the last error of the background thread since the previous
+    /// call, if any.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Notifies the background thread that the policy should be applied.
+    pub fn notify(&self) {
+        if let Some(ref sender) = self.sender {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl Drop for Retainer {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+struct Worker {
+    path:      PathBuf,
+    suffix:    Suffix,
+    retention: Retention,
+    current:   Arc<Mutex<PathBuf>>,
+    error:     Arc<Mutex<Option<io::Error>>>,
+}
+
+impl Worker {
+    fn apply(&self) {
+        if let Err(e) = self.try_apply() {
+            *self.error.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+        }
+    }
+
+    fn try_apply(&self) -> io::Result<()> {
+        // no new file can be created while we hold the lock
+        let (current, mut files) = {
+            let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            let files = scan(&self.path, self.suffix)?;
+
+            (current.clone(), files)
+        };
+
+        files.retain(|rolled| rolled.path != current);
+        files.sort_by(|a, b| (&a.period, a.index).cmp(&(&b.period, b.index)));
+
+        if let Some(compression) = self.retention.compress {
+            for rolled in files.iter_mut().filter(|rolled| !rolled.compressed) {
+                if let Some(path) = compress(&rolled.path, compression)? {
+                    rolled.path = path;
+                    rolled.compressed = true;
+                }
+            }
+        }
+
+        let mut files = files
+            .into_iter()
+            .map(|rolled| {
+                let meta = std::fs::metadata(&rolled.path)?;
+                Ok((rolled, meta.len(), meta.modified()?))
+            })
+            .collect::<io::Result<Vec<(Rolled, u64, SystemTime)>>>()?;
+
+        // files are sorted from oldest to newest
+        let mut remove = 0;
+
+        if let Some(age) = self.retention.age {
+            let now = SystemTime::now();
+
+            let expired = files
+                .iter()
+                .rposition(|&(_, _, modified)| {
+                    now.duration_since(modified)
+                        .map(|elapsed| elapsed > age.0)
+                        .unwrap_or(false)
+                })
+                .map_or(0, |pos| pos + 1);
+
+            remove = std::cmp::max(remove, expired);
+        }
+
+        if let Some(count) = self.retention.count {
+            remove = std::cmp::max(remove, files.len().saturating_sub(count));
+        }
+
+        if let Some(max) = self.retention.size {
+            let mut total = std::fs::metadata(&current).map(|meta| meta.len()).unwrap_or(0);
+            let mut excess = files.len();
+
+            for &(_, size, _) in files.iter().rev() {
+                total += size;
+
+                if total > max.bytes() {
+                    break;
+                }
+
+                excess -= 1;
+            }
+
+            remove = std::cmp::max(remove, excess);
+        }
+
+        for (rolled, _, _) in files.drain(..remove) {
+            std::fs::remove_file(&rolled.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Compresses the specified file, replacing it with its compressed version.
+///
+/// Returns the path of the compressed file or `None` if a file with this path
+/// already exists.
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "zstd")),
+    allow(unused_mut, unused_variables, unreachable_code)
+)]
+fn compress(path: &Path, compression: Compression) -> io::Result<Option<PathBuf>> {
+    let mut dest: OsString = path.to_owned().into();
+    dest.push(".");
+    dest.push(compression.extension());
+
+    let dest = PathBuf::from(dest);
+    if dest.exists() {
+        return Ok(None);
+    }
+
+    let mut tmp: OsString = dest.clone().into();
+    tmp.push(".tmp");
+
+    {
+        let mut input = File::open(path)?;
+        let output = File::create(&tmp)?;
+
+        match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use flate2::write::GzEncoder;
+
+                let mut encoder = GzEncoder::new(output, ::flate2::Compression::default());
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?.sync_all()?;
+            },
+
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                ::zstd::stream::copy_encode(&mut input, &output, 0)?;
+                output.sync_all()?;
+            },
+        }
+    }
+
+    std::fs::rename(&tmp, &dest)?;
+    std::fs::remove_file(path)?;
+
+    Ok(Some(dest))
+}
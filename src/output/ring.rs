@@ -0,0 +1,247 @@
+//! A fixed-size circular log file.
+
+use std;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+
+/// The magic bytes identifying a ring file.
+const MAGIC: &[u8; 8] = b"SLOGRING";
+
+/// The length of the header preceding the data area.
+const HEADER_LEN: u64 = 32;
+
+
+/// A writer for a preallocated file of fixed size, in which new records
+/// overwrite the oldest ones.
+///
+/// The file starts with a header of 32 bytes:
+///
+/// - the magic bytes `SLOGRING`,
+/// - the size of the data area (8 bytes, big-endian),
+/// - the offset in the data area at which the next record will be written
+///   (8 bytes, big-endian),
+/// - flags (8 bytes, big-endian), where bit 0 is set once the data area has
+///   wrapped around.
+///
+/// The header is updated at record boundaries, i.e. when the writer is
+/// flushed. Use [`read_ring`](read_ring) to read the records in chronological
+/// order.
+///
+/// An existing ring file of the same size is continued, otherwise the file is
+/// (re-)initialized.
+#[derive(Debug)]
+pub struct RingFile {
+    path:     PathBuf,
+    file:     File,
+    capacity: u64,
+    head:     u64,
+    wrapped:  bool,
+    dirty:    bool,
+}
+
+impl RingFile {
+    /// Opens or creates the ring file at the specified path with the given
+    /// total size in bytes, including the header.
+    ///
+    /// Creates all parent directories of the file if they do not exist.
+    pub fn open<P>(path: P, size: u64) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        if size <= HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("ring file size must exceed {} bytes", HEADER_LEN),
+            ));
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let capacity = size - HEADER_LEN;
+
+        let header = match file.metadata()?.len() {
+            len if len == size => read_header(&mut file)?,
+            _ => None,
+        };
+
+        let mut ring = RingFile {
+            path,
+            file,
+            capacity,
+            head: 0,
+            wrapped: false,
+            dirty: false,
+        };
+
+        match header {
+            Some(header) if header.capacity == capacity && header.head < capacity => {
+                ring.head = header.head;
+                ring.wrapped = header.wrapped;
+            },
+            _ => ring.initialize(size)?,
+        }
+
+        Ok(ring)
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the data area, i.e. the maximum number of bytes of
+    /// records retained.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Preallocates the file and writes an empty header.
+    fn initialize(&mut self, size: u64) -> io::Result<()> {
+        let zeros = [0; 64 * 1024];
+
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+
+        let mut left = size;
+        while left > 0 {
+            let n = std::cmp::min(left, zeros.len() as u64) as usize;
+            self.file.write_all(&zeros[..n])?;
+            left -= n as u64;
+        }
+
+        self.write_header()
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = [0; HEADER_LEN as usize];
+        header[0..8].copy_from_slice(MAGIC);
+        header[8..16].copy_from_slice(&self.capacity.to_be_bytes());
+        header[16..24].copy_from_slice(&self.head.to_be_bytes());
+        header[24..32].copy_from_slice(&u64::from(self.wrapped).to_be_bytes());
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Write for RingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len();
+        let mut buf = buf;
+
+        // only the last bytes of a record exceeding the capacity are retained
+        if buf.len() as u64 > self.capacity {
+            buf = &buf[buf.len() - self.capacity as usize..];
+        }
+
+        while !buf.is_empty() {
+            let n = std::cmp::min(self.capacity - self.head, buf.len() as u64) as usize;
+
+            self.file.seek(SeekFrom::Start(HEADER_LEN + self.head))?;
+            self.file.write_all(&buf[..n])?;
+
+            self.head += n as u64;
+            if self.head == self.capacity {
+                self.head = 0;
+                self.wrapped = true;
+            }
+
+            buf = &buf[n..];
+        }
+
+        self.dirty = true;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.write_header()?;
+        }
+
+        self.file.flush()
+    }
+}
+
+
+/// Reads the records of the ring file at the specified path in chronological
+/// order.
+///
+/// Records are assumed to be terminated by newlines, as written by the
+/// line-oriented default factories (e.g. `plain` or `json`). Once the ring has
+/// wrapped around, the oldest record may have been partially overwritten and
+/// is thus skipped.
+pub fn read_ring<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+
+    let header = read_header(&mut file)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a ring file"))?;
+
+    let mut data = Vec::new();
+    file.take(header.capacity).read_to_end(&mut data)?;
+
+    if data.len() as u64 != header.capacity || header.head >= header.capacity {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated ring file"));
+    }
+
+    let head = header.head as usize;
+
+    if !header.wrapped {
+        data.truncate(head);
+        return Ok(data);
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[head..]);
+    out.extend_from_slice(&data[..head]);
+
+    // skip the oldest record, which may have been partially overwritten
+    let start = out.iter().position(|&b| b == b'\n').map_or(out.len(), |pos| pos + 1);
+    out.drain(..start);
+
+    Ok(out)
+}
+
+
+struct Header {
+    capacity: u64,
+    head:     u64,
+    wrapped:  bool,
+}
+
+/// Reads the header of a ring file, returning `None` if the file does not
+/// start with a valid header.
+fn read_header(file: &mut File) -> io::Result<Option<Header>> {
+    let mut buf = [0; HEADER_LEN as usize];
+
+    file.seek(SeekFrom::Start(0))?;
+    match file.read_exact(&mut buf) {
+        Ok(()) => {},
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    if &buf[0..8] != MAGIC {
+        return Ok(None);
+    }
+
+    let u64_at = |pos: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&buf[pos..pos + 8]);
+        u64::from_be_bytes(bytes)
+    };
+
+    Ok(Some(Header {
+        capacity: u64_at(8),
+        head:     u64_at(16),
+        wrapped:  u64_at(24) & 1 != 0,
+    }))
+}
@@ -0,0 +1,434 @@
+//! A log file rotated by size and/or calendar interval.
+
+use common::{Interval, OpenMode, Retention, Rotation, Suffix};
+use output::open_file;
+use output::reopen::Reopenable;
+use output::retention::Retainer;
+
+use std;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+
+/// A writer for a log file that is rotated according to a
+/// [`Rotation`](::common::Rotation) policy.
+///
+/// **Note:** The base path itself is never written. The current file is named
+/// like all other files, by extending the base path with the configured
+/// suffix, e.g. `app.log.1`. Use a symbolic link (see
+/// [`Rotation`](::common::Rotation)) to refer to the current file via a fixed
+/// path.
+///
+/// Rotation is only performed at record boundaries, i.e. directly after the
+/// writer has been flushed. All default factories flush their output after
+/// each record.
+///
+/// Optionally, a [`Retention`](::common::Retention) policy can be applied to
+/// the rotated files. This policy is enforced in a separate thread, thus
+/// writing is not blocked by compression or removal of old files. Errors
+/// applying the policy are returned by the next flush.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path:     PathBuf,
+    rotation: Rotation,
+    file:     File,
+    current:  PathBuf,
+    period:   String,
+    index:    u64,
+    deadline: Option<i64>,
+    written:  u64,
+    boundary: bool,
+    retainer: Option<Retainer>,
+}
+
+impl RotatingFile {
+    /// Opens a rotated log file at the specified base path.
+    ///
+    /// With [`OpenMode::Append`](::common::OpenMode::Append) and
+    /// [`OpenMode::Truncate`](::common::OpenMode::Truncate), the most recent
+    /// file matching the rotation suffix will be re-used (and truncated,
+    /// respectively). With [`OpenMode::New`](::common::OpenMode::New), a new
+    /// file will always be started.
+    pub fn open<P>(path: P, mode: OpenMode, rotation: Rotation) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let now = Utc::now();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let period = period(now, &rotation);
+        let last = last_index(&path, &rotation, &period)?;
+        let first = match rotation.suffix {
+            Suffix::Index => 1,
+            Suffix::Date => 0,
+        };
+
+        let (index, mode) = match (mode, last) {
+            (OpenMode::New, Some(index)) => (index + 1, OpenMode::New),
+            (mode, last) => (last.unwrap_or(first), mode),
+        };
+
+        let (file, current, index) = open_new(&path, &rotation, &period, index, mode)?;
+        let meta = file.metadata()?;
+
+        let mut deadline = rotation.interval.map(|i| next_boundary(now.timestamp(), i));
+
+        // an old file from a previous interval should be rotated on the first
+        // write
+        if let (Some(interval), Ok(modified)) = (rotation.interval, meta.modified()) {
+            let modified: DateTime<Utc> = modified.into();
+            let start = next_boundary(now.timestamp(), interval) - interval.seconds();
+
+            if meta.len() > 0 && modified.timestamp() < start {
+                deadline = Some(now.timestamp());
+            }
+        }
+
+        let rotating = RotatingFile {
+            path,
+            rotation,
+            file,
+            current,
+            period,
+            index,
+            deadline,
+            written: meta.len(),
+            boundary: true,
+            retainer: None,
+        };
+
+        rotating.link()?;
+        Ok(rotating)
+    }
+
+    /// Applies the specified retention policy to all files created by this
+    /// writer, including already existing ones.
+    ///
+    /// Replaces any previously set policy.
+    pub fn with_retention(mut self, retention: Retention) -> io::Result<Self> {
+        self.retainer.take();
+
+        let retainer = Retainer::spawn(
+            self.path.clone(),
+            self.rotation.suffix,
+            self.current.clone(),
+            retention,
+        )?;
+
+        self.retainer = Some(retainer);
+        Ok(self)
+    }
+
+    /// Returns the base path of this rotated file.
+    pub fn base_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the file that is currently being written to.
+    pub fn path(&self) -> &Path {
+        &self.current
+    }
+
+    /// Returns the rotation policy of this file.
+    pub fn rotation(&self) -> &Rotation {
+        &self.rotation
+    }
+
+    /// Closes the current file and starts a new one.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        let now = Utc::now();
+
+        self.file.flush()?;
+
+        if let Some(interval) = self.rotation.interval {
+            self.deadline = Some(next_boundary(now.timestamp(), interval));
+        }
+
+        match self.rotation.suffix {
+            Suffix::Index => self.index += 1,
+            Suffix::Date => {
+                let period = period(now, &self.rotation);
+
+                if period != self.period {
+                    self.period = period;
+                    self.index = 0;
+                } else {
+                    self.index += 1;
+                }
+            },
+        }
+
+        self.open_next()
+    }
+
+    fn is_due(&self) -> bool {
+        let size = self.rotation
+            .size
+            .map_or(false, |max| self.written > 0 && self.written >= max.bytes());
+
+        let time = self.deadline
+            .map_or(false, |deadline| Utc::now().timestamp() >= deadline);
+
+        size || time
+    }
+
+    fn open_next(&mut self) -> io::Result<()> {
+        {
+            let guard = self.retainer.as_ref().map(|retainer| retainer.lock());
+
+            let (file, current, index) =
+                open_new(&self.path, &self.rotation, &self.period, self.index, OpenMode::New)?;
+
+            self.file = file;
+            self.current = current;
+            self.index = index;
+            self.written = 0;
+
+            if let Some(mut guard) = guard {
+                *guard = self.current.clone();
+            }
+        }
+
+        if let Some(ref retainer) = self.retainer {
+            retainer.notify();
+        }
+
+        self.link()
+    }
+
+    /// Updates the symbolic link pointing to the current file, if requested.
+    fn link(&self) -> io::Result<()> {
+        let link = match self.rotation.symlink {
+            Some(ref link) => link,
+            None => return Ok(()),
+        };
+
+        let target = if link.parent() == self.current.parent() {
+            PathBuf::from(self.current.file_name().unwrap())
+        } else if self.current.is_absolute() {
+            self.current.clone()
+        } else {
+            std::env::current_dir()?.join(&self.current)
+        };
+
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // replace the link atomically
+        let mut tmp: OsString = link.clone().into();
+        tmp.push(".tmp");
+
+        let _ = std::fs::remove_file(&tmp);
+        symlink(&target, &tmp)?;
+        std::fs::rename(&tmp, link)
+    }
+}
+
+impl Reopenable for RotatingFile {
+    fn path(&self) -> &Path {
+        &self.current
+    }
+
+    fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = open_file(&self.current, OpenMode::Append)?;
+        self.written = self.file.metadata()?.len();
+        self.link()
+    }
+
+    fn prepare(&mut self) -> io::Result<()> {
+        if self.boundary {
+            self.boundary = false;
+
+            if self.is_due() {
+                self.rotate()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.prepare()?;
+
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.boundary = true;
+        let result = self.file.flush();
+
+        match self.retainer.as_ref().and_then(Retainer::take_error) {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+
+/// Opens the file with the specified index.
+///
+/// If the mode is `OpenMode::New` and the file already exists, the index will
+/// be increased until a new file can be created. Returns the file, its path,
+/// and its actual index.
+fn open_new(path: &Path, rotation: &Rotation, period: &str, mut index: u64, mode: OpenMode)
+    -> io::Result<(File, PathBuf, u64)>
+{
+    loop {
+        let current = name(path, rotation, period, index);
+
+        match open_file(&current, mode) {
+            Ok(file) => return Ok((file, current, index)),
+            Err(ref e) if mode == OpenMode::New && e.kind() == io::ErrorKind::AlreadyExists => {
+                index += 1
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns the name of the file with the specified index.
+fn name(path: &Path, rotation: &Rotation, period: &str, index: u64) -> PathBuf {
+    let mut name: OsString = path.to_owned().into();
+
+    match rotation.suffix {
+        Suffix::Index => name.push(format!(".{}", index)),
+        Suffix::Date if index == 0 => name.push(format!(".{}", period)),
+        Suffix::Date => name.push(format!(".{}.{}", period, index)),
+    }
+
+    name.into()
+}
+
+/// Returns the index following the most recent existing file for the
+/// specified period, or `None` if no such file exists.
+///
+/// If the most recent file is not compressed, its index is returned instead, so
+/// that it may be re-used.
+fn last_index(path: &Path, rotation: &Rotation, period: &str) -> io::Result<Option<u64>> {
+    let last = scan(path, rotation.suffix)?
+        .into_iter()
+        .filter(|rolled| rotation.suffix == Suffix::Index || rolled.period == period)
+        .map(|rolled| rolled.index + if rolled.compressed { 1 } else { 0 })
+        .max();
+
+    Ok(last)
+}
+
+
+/// A file created by a `RotatingFile`, identified by its suffix.
+#[derive(Debug)]
+pub(crate) struct Rolled {
+    pub path:       PathBuf,
+    pub period:     String,
+    pub index:      u64,
+    pub compressed: bool,
+}
+
+/// Returns all files that have been created by a `RotatingFile` with the
+/// specified base path and suffix.
+pub(crate) fn scan(path: &Path, suffix: Suffix) -> io::Result<Vec<Rolled>> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    let prefix = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => format!("{}.", name),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_symlink() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let rest = match name.to_str() {
+            Some(name) if name.starts_with(&prefix) => &name[prefix.len()..],
+            _ => continue,
+        };
+
+        let (rest, compressed) = match rest.rfind('.') {
+            Some(pos) if rest[pos + 1..] == *"gz" || rest[pos + 1..] == *"zst" => {
+                (&rest[..pos], true)
+            },
+            _ => (rest, false),
+        };
+
+        let parsed = match suffix {
+            Suffix::Index => rest.parse().ok().map(|index| (String::new(), index)),
+            Suffix::Date => {
+                let (period, index) = match rest.find('.') {
+                    Some(pos) => (&rest[..pos], rest[pos + 1..].parse().ok()),
+                    None => (rest, Some(0)),
+                };
+
+                let valid = period.starts_with(|c: char| c.is_ascii_digit())
+                    && period.chars().all(|c| c.is_ascii_digit() || c == '-' || c == 'T');
+
+                index.filter(|_| valid).map(|index| (period.to_owned(), index))
+            },
+        };
+
+        if let Some((period, index)) = parsed {
+            files.push(Rolled {
+                path: entry.path(),
+                period,
+                index,
+                compressed,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns the period-string for the date-suffix at the specified time.
+fn period(now: DateTime<Utc>, rotation: &Rotation) -> String {
+    match rotation.interval {
+        Some(Interval::Hourly) => now.format("%Y-%m-%dT%H").to_string(),
+        Some(Interval::Daily) | None => now.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Returns the timestamp of the next interval boundary after `now`.
+fn next_boundary(now: i64, interval: Interval) -> i64 {
+    let len = interval.seconds();
+    now - ((now % len) + len) % len + len
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dst: &OsString) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(windows)]
+fn symlink(src: &Path, dst: &OsString) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(src, dst)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_src: &Path, _dst: &OsString) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "symbolic links are not supported"))
+}
@@ -0,0 +1,347 @@
+//! Reloading of the logging configuration at runtime.
+//!
+//! A [`Reloadable`](Reloadable) drain forwards all records to an inner drain
+//! that can be replaced at any time, while the `Logger` built on top of it
+//! keeps working. The function [`watch`](watch) creates such a drain from a
+//! configuration file and replaces its inner drain whenever the file changes
+//! or, optionally, on `SIGHUP`.
+//!
+//! On each reload, the configuration is loaded via a user-provided function
+//! (e.g. deserializing the file contents via the default deserializers) and
+//! built via the default drain factories (see [`drains()`](::drains)). If
+//! either fails, the previous drain is kept and the error is logged to it.
+//!
+//! Outputs of the previous drain whose target is configured identically in
+//! the new configuration are not opened again, instead the new drain continues
+//! to write to them (see [`Outputs`](::output::Outputs)). Thus, reloading
+//! does not truncate or re-create files and a rotated file is never written by
+//! two drains. All other outputs of the previous drain are closed once it has
+//! been replaced.
+//!
+//! The resulting drain can be wrapped in an `Async` drain like any other
+//! drain. Have a look at the `reload` example for more details.
+
+use {BoxDrain, Config, Context, Error};
+pub use common::Duration;
+use drain::LevelHandle;
+use output::Outputs;
+use signal::{Signal, Watch};
+
+use std;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use slog::{Drain, Level, OwnedKVList, Record};
+
+
+/// Options describing when a configuration file should be reloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reload {
+    /// The interval in which the file should be checked for changes, `None`
+    /// to disable polling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Duration>,
+
+    /// If set to `true`, reload the file on `SIGHUP`.
+    #[serde(default)]
+    pub signal: bool,
+}
+
+impl Default for Reload {
+    fn default() -> Self {
+        Reload {
+            interval: Some(Duration::from_secs(5)),
+            signal: false,
+        }
+    }
+}
+
+
+/// A `Drain` forwarding all records to an inner drain that can be replaced at
+/// runtime.
+///
+/// Clones of this drain share the same inner drain. The level handles and
+/// outputs of all drains built via [`build`](Reloadable::build) and
+/// [`rebuild`](Reloadable::rebuild) are kept across rebuilds, i.e. a handle
+/// retrieved via [`handle`](Reloadable::handle) stays attached to the drains
+/// built from subsequent configurations.
+#[derive(Clone)]
+pub struct Reloadable {
+    inner: Arc<Mutex<BoxDrain>>,
+    built: Arc<Mutex<Built>>,
+}
+
+/// The state of the last build, carried over to the next one.
+#[derive(Default)]
+struct Built {
+    handles: BTreeMap<String, LevelHandle>,
+    outputs: Outputs,
+}
+
+impl Reloadable {
+    /// Creates a new drain forwarding all records to the specified drain.
+    ///
+    /// The outputs of the specified drain are unknown, thus they cannot be
+    /// reused by a subsequent rebuild. Use [`build`](Reloadable::build) to
+    /// create the initial drain from a configuration instead.
+    pub fn new(drain: BoxDrain) -> Self {
+        Reloadable {
+            inner: Arc::new(Mutex::new(drain)),
+            built: Arc::new(Mutex::new(Built::default())),
+        }
+    }
+
+    /// Builds a drain from the specified configuration via the default drain
+    /// factories and creates a new drain forwarding all records to it.
+    pub fn build(cfg: &Config) -> Result<Self, Error> {
+        let ctx = Context::default();
+        let drain = Reloadable::new(ctx.build(cfg)?);
+
+        {
+            let mut built = drain.built();
+            built.handles = ctx.handles();
+            built.outputs = ctx.outputs();
+        }
+
+        Ok(drain)
+    }
+
+    /// Returns the level handle with the specified name, creating it if it
+    /// does not exist yet.
+    ///
+    /// The handle is attached to the drains built via
+    /// [`rebuild`](Reloadable::rebuild) from configurations referring to it.
+    pub fn handle(&self, name: &str) -> LevelHandle {
+        self.built()
+            .handles
+            .entry(name.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// Replaces the inner drain, returning the previous one.
+    ///
+    /// Records are either passed to the previous or the new drain, no records
+    /// are lost during the swap.
+    pub fn swap(&self, drain: BoxDrain) -> BoxDrain {
+        std::mem::replace(&mut *self.lock(), drain)
+    }
+
+    /// Builds a drain from the specified configuration via the default drain
+    /// factories and replaces the inner drain with it.
+    ///
+    /// Outputs of the previous build are reused for identically configured
+    /// targets, all other outputs of the inner drain are closed once it has
+    /// been replaced. Keeps the inner drain if the new drain cannot be built.
+    pub fn rebuild(&self, cfg: &Config) -> Result<(), Error> {
+        let ctx = {
+            let built = self.built();
+
+            Context::default()
+                .with_handles(built.handles.clone())
+                .with_outputs(built.outputs.clone())
+        };
+
+        let drain = ctx.build(cfg)?;
+
+        {
+            let mut built = self.built();
+            built.handles.extend(ctx.handles());
+            built.outputs = ctx.outputs();
+        }
+
+        // drop the previous drain outside of the lock
+        let _ = self.swap(drain);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BoxDrain> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn built(&self) -> MutexGuard<'_, Built> {
+        self.built.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drain for Reloadable {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        self.lock().log(record, values)
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.lock().is_enabled(level)
+    }
+}
+
+
+/// A background thread reloading the configuration of a `Reloadable` drain.
+///
+/// Dropping this value stops the thread.
+#[derive(Debug)]
+pub struct Watcher {
+    sender: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+/// Creates a `Reloadable` drain from the specified configuration file and
+/// spawns a thread reloading it according to the given options.
+///
+/// The configuration is loaded via the given function and built via the
+/// default drain factories, both initially and on each reload. Errors during
+/// the initial load are returned, errors during a reload are logged to the
+/// current drain, which is kept in this case.
+///
+/// Returns an error if reloading on `SIGHUP` has been requested but is not
+/// supported.
+///
+/// Use [`watch_with`](watch_with) to be notified of errors during a reload
+/// directly, e.g. in case the current drain fails as well.
+pub fn watch<P, F>(path: P, reload: Reload, load: F) -> Result<(Reloadable, Watcher), Error>
+where
+    P: Into<PathBuf>,
+    F: Fn(&Path) -> Result<Box<Config>, Error> + Send + 'static,
+{
+    watch_with(path, reload, load, |_| ())
+}
+
+/// Creates a `Reloadable` drain from the specified configuration file and
+/// spawns a thread reloading it according to the given options, passing all
+/// errors during a reload to the specified callback.
+///
+/// This works like [`watch`](watch), except that the callback is invoked with
+/// each error during a reload in addition to the error being logged to the
+/// current drain. The callback is invoked on the reloading thread.
+pub fn watch_with<P, F, E>(
+    path: P,
+    reload: Reload,
+    load: F,
+    on_error: E,
+) -> Result<(Reloadable, Watcher), Error>
+where
+    P: Into<PathBuf>,
+    F: Fn(&Path) -> Result<Box<Config>, Error> + Send + 'static,
+    E: Fn(&Error) + Send + 'static,
+{
+    let path = path.into();
+
+    let signal = if reload.signal {
+        Some(Watch::new(Signal::Hup)?)
+    } else {
+        None
+    };
+
+    let modified = std::fs::metadata(&path).ok().and_then(|meta| stamp(&meta));
+    let drain = Reloadable::build(load(&path)?.as_ref())?;
+
+    let mut worker = Worker {
+        path,
+        load,
+        on_error,
+        drain: drain.clone(),
+        signal,
+        modified,
+        poll: reload.interval.is_some(),
+    };
+
+    let tick = reload.interval.map_or(std::time::Duration::from_secs(1), Into::into);
+    let (sender, receiver) = channel();
+
+    let thread = std::thread::Builder::new()
+        .name("slog-conf-reload".into())
+        .spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(tick) {
+                worker.check();
+            }
+        })?;
+
+    let watcher = Watcher {
+        sender: Some(sender),
+        thread: Some(thread),
+    };
+
+    Ok((drain, watcher))
+}
+
+
+struct Worker<F, E> {
+    path:     PathBuf,
+    load:     F,
+    on_error: E,
+    drain:    Reloadable,
+    signal:   Option<Watch>,
+    modified: Option<(SystemTime, u64)>,
+    poll:     bool,
+}
+
+impl<F, E> Worker<F, E>
+where
+    F: Fn(&Path) -> Result<Box<Config>, Error>,
+    E: Fn(&Error),
+{
+    fn check(&mut self) {
+        let signaled = self.signal.as_mut().map_or(false, |watch| watch.poll() > 0);
+
+        let modified = if self.poll {
+            let modified = std::fs::metadata(&self.path).ok().and_then(|meta| stamp(&meta));
+            let changed = modified.is_some() && modified != self.modified;
+
+            self.modified = modified;
+            changed
+        } else {
+            false
+        };
+
+        if signaled || modified {
+            if let Err(e) = self.reload() {
+                self.report(&e);
+            }
+        }
+    }
+
+    fn reload(&self) -> Result<(), Error> {
+        let cfg = (self.load)(&self.path)?;
+        self.drain.rebuild(cfg.as_ref())
+    }
+
+    fn report(&self, error: &Error) {
+        (self.on_error)(error);
+
+        let error = error.to_string();
+        let values = OwnedKVList::from(o!());
+
+        let _ = self.drain.log(
+            &record!(
+                Level::Error,
+                "",
+                &format_args!("failed to reload logging configuration, keeping previous one"),
+                b!("path" => %self.path.display(), "error" => &error)
+            ),
+            &values,
+        );
+    }
+}
+
+
+fn stamp(meta: &Metadata) -> Option<(SystemTime, u64)> {
+    meta.modified().ok().map(|modified| (modified, meta.len()))
+}
@@ -0,0 +1,115 @@
+//! Process-wide notification of received signals.
+//!
+//! Signal handlers are registered lazily and are never removed. Any handlers
+//! already registered by the application are preserved.
+
+use Error;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+/// A signal that can be watched.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Signal {
+    /// `SIGHUP`.
+    Hup,
+
+    /// `SIGUSR1`.
+    Usr1,
+
+    /// `SIGUSR2`.
+    Usr2,
+}
+
+
+/// A watch to check whether a signal has been received since the last check.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    counter: Arc<AtomicUsize>,
+    seen:    usize,
+}
+
+impl Watch {
+    /// Creates a new watch for the specified signal, registering a handler
+    /// for this signal if necessary.
+    ///
+    /// Returns an error if signals are not supported on this platform or the
+    /// `signal` feature is disabled.
+    pub fn new(signal: Signal) -> Result<Self, Error> {
+        let counter = imp::counter(signal)?;
+        let seen = counter.load(Ordering::SeqCst);
+
+        Ok(Watch { counter, seen })
+    }
+
+    /// Returns the number of times the signal has been received since the
+    /// last call to this function.
+    pub fn poll(&mut self) -> usize {
+        let count = self.counter.load(Ordering::SeqCst);
+        let new = count.wrapping_sub(self.seen);
+
+        self.seen = count;
+        new
+    }
+}
+
+
+#[cfg(all(unix, feature = "signal"))]
+mod imp {
+    use super::Signal;
+    use Error;
+
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use signal_hook;
+
+
+    pub fn counter(signal: Signal) -> Result<Arc<AtomicUsize>, Error> {
+        lazy_static! {
+            static ref COUNTERS: Mutex<HashMap<Signal, Arc<AtomicUsize>>> =
+                Mutex::new(HashMap::new());
+        }
+
+        let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(counter) = counters.get(&signal) {
+            return Ok(counter.clone());
+        }
+
+        let id = match signal {
+            Signal::Hup => signal_hook::SIGHUP,
+            Signal::Usr1 => signal_hook::SIGUSR1,
+            Signal::Usr2 => signal_hook::SIGUSR2,
+        };
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = counter.clone();
+
+        // only performs an atomic operation, which is signal-safe
+        unsafe {
+            signal_hook::register(id, move || {
+                handler.fetch_add(1, Ordering::SeqCst);
+            })?;
+        }
+
+        counters.insert(signal, counter.clone());
+        Ok(counter)
+    }
+}
+
+#[cfg(not(all(unix, feature = "signal")))]
+mod imp {
+    use super::Signal;
+    use Error;
+
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+
+    pub fn counter(_signal: Signal) -> Result<Arc<AtomicUsize>, Error> {
+        Err(Error::msg("signal handling is not supported"))
+    }
+}
@@ -0,0 +1,262 @@
+//! The database schema used by loggers of type `sqlite` and a query helper to
+//! read records back.
+//!
+//! Records are stored in a single table:
+//!
+//! ```sql
+//! CREATE TABLE records (
+//!     id     INTEGER PRIMARY KEY,
+//!     ts     INTEGER NOT NULL,  -- nanoseconds since the UNIX epoch
+//!     level  TEXT NOT NULL,     -- e.g. 'info'
+//!     module TEXT NOT NULL,
+//!     tag    TEXT NOT NULL,     -- empty if none
+//!     msg    TEXT NOT NULL,
+//!     kv     TEXT NOT NULL      -- JSON object of key-value pairs
+//! );
+//! CREATE INDEX records_ts ON records (ts);
+//! ```
+//!
+//! The `kv` column contains the key-value pairs of the record and logger as
+//! JSON object with string values. If a key occurs more than once, the pair of
+//! the record takes precedence. The database is opened in WAL mode, thus it
+//! can be read while the logger is writing to it.
+//!
+//! ```no_run
+//! use slog_conf::common::Level;
+//! use slog_conf::sqlite::Query;
+//!
+//! let records = Query::new()
+//!     .level(Level::Warning)
+//!     .key_value("request", "c0ffee")
+//!     .fetch("/var/log/app.db")
+//!     .unwrap();
+//!
+//! for record in records {
+//!     println!("{} {} {}", record.time, record.msg, record.kv["request"]);
+//! }
+//! ```
+
+use Error;
+use common::Level;
+
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::{Connection, OpenFlags};
+use rusqlite::types::Value as SqlValue;
+
+use chrono::{DateTime, Utc};
+
+
+/// Statements creating the schema, if it does not exist.
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS records (
+        id     INTEGER PRIMARY KEY,
+        ts     INTEGER NOT NULL,
+        level  TEXT NOT NULL,
+        module TEXT NOT NULL,
+        tag    TEXT NOT NULL,
+        msg    TEXT NOT NULL,
+        kv     TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS records_ts ON records (ts);
+";
+
+/// Statement inserting a single record.
+pub(crate) const INSERT: &str =
+    "INSERT INTO records (ts, level, module, tag, msg, kv) VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
+
+/// The time to wait for a lock held by another connection.
+pub(crate) const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// All levels, ordered from most to least severe.
+const LEVELS: [Level; 6] = [
+    Level::Critical,
+    Level::Error,
+    Level::Warning,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+
+/// A record read from the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// The row id of the record, increasing with each record.
+    pub id: i64,
+
+    /// The time at which the record has been logged.
+    pub time: DateTime<Utc>,
+
+    /// The level of the record.
+    pub level: Level,
+
+    /// The module in which the record has been logged.
+    pub module: String,
+
+    /// The tag of the record, empty if none.
+    pub tag: String,
+
+    /// The message of the record.
+    pub msg: String,
+
+    /// The key-value pairs of the record and logger.
+    pub kv: serde_json::Map<String, serde_json::Value>,
+}
+
+
+/// A query for records, matching all records by default.
+///
+/// Records are returned in the order in which they have been logged.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    level: Option<Level>,
+    key:   Option<String>,
+    value: Option<String>,
+    limit: Option<u32>,
+}
+
+impl Query {
+    /// Creates a new query matching all records.
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Only match records logged at or after the specified time.
+    pub fn since(mut self, time: DateTime<Utc>) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// Only match records logged before the specified time.
+    pub fn until(mut self, time: DateTime<Utc>) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Only match records at least as severe as the specified level.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Only match records having a key-value pair with the specified key.
+    pub fn key(mut self, key: &str) -> Self {
+        self.key = Some(key.into());
+        self.value = None;
+        self
+    }
+
+    /// Only match records having the specified key-value pair.
+    pub fn key_value(mut self, key: &str, value: &str) -> Self {
+        self.key = Some(key.into());
+        self.value = Some(value.into());
+        self
+    }
+
+    /// Return at most the specified number of records, i.e. the oldest
+    /// matching ones.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Runs this query on the database at the specified path.
+    ///
+    /// The database is opened read-only.
+    pub fn fetch<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Record>, Error> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(path, flags).map_err(|e| Error::msg(&e))?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| Error::msg(&e))?;
+
+        self.fetch_from(&conn).map_err(|e| Error::msg(&e))
+    }
+
+    fn fetch_from(&self, conn: &Connection) -> rusqlite::Result<Vec<Record>> {
+        let mut sql = String::from("SELECT id, ts, level, module, tag, msg, kv FROM records WHERE 1");
+        let mut params = Vec::new();
+
+        if let Some(since) = self.since {
+            sql.push_str(" AND ts >= ?");
+            params.push(SqlValue::Integer(nanos(since)));
+        }
+
+        if let Some(until) = self.until {
+            sql.push_str(" AND ts < ?");
+            params.push(SqlValue::Integer(nanos(until)));
+        }
+
+        if let Some(level) = self.level {
+            let level: slog::Level = level.into();
+            let names: Vec<_> = LEVELS
+                .iter()
+                .filter(|&&l| Into::<slog::Level>::into(l).is_at_least(level))
+                .map(|l| SqlValue::Text(l.to_str_lowercase().into()))
+                .collect();
+
+            sql.push_str(" AND level IN (");
+            sql.push_str(&vec!["?"; names.len()].join(", "));
+            sql.push(')');
+            params.extend(names);
+        }
+
+        if let Some(ref key) = self.key {
+            sql.push_str(" AND EXISTS (SELECT 1 FROM json_each(records.kv) WHERE key = ?");
+            params.push(SqlValue::Text(key.clone()));
+
+            if let Some(ref value) = self.value {
+                sql.push_str(" AND value = ?");
+                params.push(SqlValue::Text(value.clone()));
+            }
+
+            sql.push(')');
+        }
+
+        sql.push_str(" ORDER BY id");
+
+        if let Some(limit) = self.limit {
+            sql.push_str(" LIMIT ?");
+            params.push(SqlValue::Integer(i64::from(limit)));
+        }
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            let level: String = row.get(2)?;
+            let kv: String = row.get(6)?;
+
+            let level = level.parse::<slog::Level>().map(Level::from).map_err(|_| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    format!("invalid level '{}'", level).into(),
+                )
+            })?;
+
+            let kv = match serde_json::from_str(&kv) {
+                Ok(serde_json::Value::Object(kv)) => kv,
+                _ => serde_json::Map::new(),
+            };
+
+            Ok(Record {
+                id: row.get(0)?,
+                time: DateTime::from_timestamp_nanos(row.get(1)?),
+                level,
+                module: row.get(3)?,
+                tag: row.get(4)?,
+                msg: row.get(5)?,
+                kv,
+            })
+        })?;
+
+        rows.collect()
+    }
+}
+
+
+/// Returns the specified time in nanoseconds since the UNIX epoch.
+pub(crate) fn nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp() * 1_000_000_000 + i64::from(time.timestamp_subsec_nanos())
+}
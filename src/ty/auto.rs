@@ -0,0 +1,159 @@
+//! Configuration for an `auto`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type chooses between two nested loggers when it is built:
+//! the interactive logger (by default of type `term`) is used if the
+//! configured standard stream is a terminal, the non-interactive logger (by
+//! default of type `json`) is used otherwise. The non-interactive logger is
+//! also used if any of the configured environment variables is set to a
+//! non-empty value, by default `CI` and `KUBERNETES_SERVICE_HOST`. This allows
+//! a single configuration to serve both developers and deployments.
+//!
+//! The default factories build the drain of the chosen configuration within
+//! the context they are invoked in, by default via the default drain factories
+//! (see [`Context`](::Context)). The `Async` factory wraps this drain in an
+//! `Async` drain.
+
+use {BoxDrain, Context, Error};
+use ty::{json, term};
+pub use common::{AsyncWorker, TermTarget as Stream};
+
+use std;
+use std::io::IsTerminal;
+
+use slog::Drain;
+use slog_async::{Async, AsyncGuard};
+
+
+/// Configuration for a logger of type `auto`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The configuration of the logger used if the output is interactive.
+    ///
+    /// Defaults to a `term` logger with default settings.
+    #[serde(default = "default::interactive")]
+    pub interactive: Box<::Config>,
+
+    /// The configuration of the logger used if the output is not interactive.
+    ///
+    /// Defaults to a `json` logger with default settings.
+    #[serde(default = "default::non_interactive", rename = "non-interactive")]
+    pub non_interactive: Box<::Config>,
+
+    /// The standard stream which is checked for being a terminal.
+    ///
+    /// This should be the stream the interactive logger writes to.
+    #[serde(default)]
+    pub stream: Stream,
+
+    /// Environment variables indicating non-interactive output if set to a
+    /// non-empty value.
+    #[serde(default = "default::env")]
+    pub env: Vec<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl Config {
+    /// Returns `true` if the interactive logger should be used, i.e. if none
+    /// of the environment variables is set and the stream is a terminal.
+    pub fn is_interactive(&self) -> bool {
+        let hinted = self.env.iter().any(|var| {
+            std::env::var_os(var).map_or(false, |value| !value.is_empty())
+        });
+
+        if hinted {
+            return false;
+        }
+
+        match self.stream {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Returns the configuration of the logger that should be used.
+    pub fn choose(&self) -> &::Config {
+        if self.is_interactive() {
+            self.interactive.as_ref()
+        } else {
+            self.non_interactive.as_ref()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            interactive: default::interactive(),
+            non_interactive: default::non_interactive(),
+            stream: Stream::default(),
+            env: default::env(),
+            worker: AsyncWorker::default(),
+        }
+    }
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "auto"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `auto`.
+///
+/// The chosen drain is built via the registry of the build context.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `auto`.
+///
+/// The chosen drain is built via the registry of the build context.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    ctx.build(cfg.choose())
+}
+
+
+mod default {
+    use super::{json, term};
+
+    pub fn interactive() -> Box<::Config> { Box::new(term::Config::default()) }
+    pub fn non_interactive() -> Box<::Config> { Box::new(json::Config::default()) }
+
+    pub fn env() -> Vec<String> {
+        vec!["CI".into(), "KUBERNETES_SERVICE_HOST".into()]
+    }
+}
@@ -0,0 +1,140 @@
+//! Configuration for a `binary`-type logger and corresponding factories for
+//! an `Async` drain and a synchronous drain.
+//!
+//! A logger of this type writes each record as compact, length-delimited
+//! MessagePack or CBOR map, preserving the types of key-value pairs. See the
+//! [`binary`](::binary) module for the format and for a reader of such files,
+//! e.g. to convert them to JSON.
+//!
+//! Network and ring targets are not supported, as records are not
+//! line-oriented.
+
+use {BoxDrain, Context, Error};
+use binary;
+use drain::LevelFilter;
+use output::Opened;
+pub use binary::Encoding;
+pub use common::{AsyncWorker, Directives, Level, OpenMode, Rotation, Target};
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::Utc;
+
+
+/// Configuration for a logger of type `binary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct Config {
+    /// The target to which the logger should write.
+    #[serde(default)]
+    pub target: Target,
+
+    /// The binary encoding of records.
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "binary"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `binary`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `binary`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    match cfg.target {
+        Target::Network { .. } => {
+            return Err(Error::msg("binary logger does not support network targets"));
+        },
+        Target::Ring { .. } => {
+            return Err(Error::msg("binary logger does not support ring targets"));
+        },
+        _ => {},
+    }
+
+    let drain = Binary {
+        output:   Mutex::new(ctx.open(&cfg.target)?),
+        encoding: cfg.encoding,
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+/// A `Drain` writing length-delimited binary records.
+struct Binary {
+    output:   Mutex<Opened>,
+    encoding: Encoding,
+}
+
+impl Drain for Binary {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let buf = binary::encode(self.encoding, Utc::now(), record, values);
+
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        output.write_all(&buf)?;
+        output.flush()
+    }
+}
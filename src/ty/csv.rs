@@ -0,0 +1,336 @@
+//! Configuration for a `csv`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type writes a header row followed by one row per record.
+//! The columns are configurable, each column is either one of
+//!
+//! - `ts`: the timestamp,
+//! - `level`: the level of the record,
+//! - `msg`: the message of the record,
+//! - `module`: the module in which the record has been logged,
+//! - `tag`: the tag of the record,
+//!
+//! or the key of a key-value pair of the record or logger. Columns for missing
+//! pairs are left empty. If a pair occurs more than once, the pair of the
+//! record takes precedence over the pair of the logger. Optionally, all
+//! remaining pairs can be written as JSON object to an additional, last
+//! column.
+//!
+//! Fields are separated by a comma or a tab and quoted according to RFC 4180,
+//! i.e. fields containing the delimiter, quotes, or line breaks are enclosed
+//! in quotes and contained quotes are doubled. Rows are terminated by CRLF.
+//!
+//! When writing to a file, the header row is written whenever the file is
+//! empty. Thus, appending to an existing file does not repeat the header,
+//! whereas each rotated file starts with its own header. Ring files do not
+//! contain a header row.
+
+use {BoxDrain, Context, Error};
+use drain::{LevelFilter, Reporting};
+use kv;
+use output::Opened;
+pub use common::{AsyncWorker, Directives, Level, OpenMode, Rotation, Target, Timestamp};
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::{Local, Utc};
+
+
+/// Configuration for a logger of type `csv`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The target to which the logger should write.
+    #[serde(default)]
+    pub target: Target,
+
+    /// The columns of each row.
+    #[serde(default = "default::columns")]
+    pub columns: Vec<String>,
+
+    /// The name of the column containing all remaining key-value pairs as JSON
+    /// object, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rest: Option<String>,
+
+    /// The field delimiter.
+    #[serde(default)]
+    pub delimiter: Delimiter,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The timestamp format.
+    #[serde(default)]
+    pub timestamp: Timestamp,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            target: Target::default(),
+            columns: default::columns(),
+            rest: None,
+            delimiter: Delimiter::default(),
+            level: Directives::default(),
+            handle: None,
+            timestamp: Timestamp::default(),
+            worker: AsyncWorker::default(),
+        }
+    }
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "csv"
+    }
+}
+
+
+/// The field delimiter.
+///
+/// Defaults to [`Comma`](Delimiter::Comma).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delimiter {
+    /// Separate fields by a comma (CSV).
+    Comma,
+
+    /// Separate fields by a tab (TSV).
+    Tab,
+}
+
+impl Delimiter {
+    fn as_char(self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Tab => '\t',
+        }
+    }
+}
+
+impl Default for Delimiter {
+    fn default() -> Self {
+        Delimiter::Comma
+    }
+}
+
+
+/// Factory for an `Async` drain of type `csv`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `csv`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.columns.is_empty() && cfg.rest.is_none() {
+        return Err(Error::msg("csv logger requires at least one column"));
+    }
+
+    let columns: Vec<Column> = cfg.columns.iter().map(|name| Column::from(name.as_str())).collect();
+    let delimiter = cfg.delimiter.as_char();
+
+    let mut header = String::new();
+    let names = cfg.columns.iter().chain(cfg.rest.iter());
+    row(&mut header, delimiter, names.map(String::as_str));
+
+    let output = ctx.open_with_header(&cfg.target, header.as_bytes())?;
+    let dropped = output.dropped();
+
+    let drain = Csv {
+        output: Mutex::new(output),
+        columns,
+        rest: cfg.rest.is_some(),
+        delimiter,
+        timestamp: cfg.timestamp,
+    };
+
+    let filter = LevelFilter::new(Reporting::new(drain, dropped), cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    pub fn columns() -> Vec<String> {
+        vec!["ts".into(), "level".into(), "msg".into()]
+    }
+}
+
+
+/// The content of a column.
+enum Column {
+    Timestamp,
+    Level,
+    Message,
+    Module,
+    Tag,
+    Key(String),
+}
+
+impl<'a> From<&'a str> for Column {
+    fn from(name: &'a str) -> Self {
+        match name {
+            "ts" => Column::Timestamp,
+            "level" => Column::Level,
+            "msg" => Column::Message,
+            "module" => Column::Module,
+            "tag" => Column::Tag,
+            key => Column::Key(key.to_owned()),
+        }
+    }
+}
+
+
+/// A `Drain` writing records as CSV rows.
+struct Csv {
+    output:    Mutex<Opened>,
+    columns:   Vec<Column>,
+    rest:      bool,
+    delimiter: char,
+    timestamp: Timestamp,
+}
+
+impl Csv {
+    fn format(&self, record: &Record, values: &OwnedKVList) -> String {
+        let time = match self.timestamp {
+            Timestamp::Rfc3339Utc => Utc::now().to_rfc3339(),
+            Timestamp::Rfc3339Local => Local::now().to_rfc3339(),
+        };
+
+        let msg = record.msg().to_string();
+        let pairs = kv::collect(record, values);
+
+        let mut fields: Vec<&str> = self.columns
+            .iter()
+            .map(|column| match *column {
+                Column::Timestamp => time.as_str(),
+                Column::Level => Level::from(record.level()).to_str_lowercase(),
+                Column::Message => msg.as_str(),
+                Column::Module => record.module(),
+                Column::Tag => record.tag(),
+                Column::Key(ref key) => pairs
+                    .iter()
+                    .find(|&(k, _)| k == key)
+                    .map_or("", |(_, v)| v.as_str()),
+            })
+            .collect();
+
+        let rest = if self.rest {
+            let mut obj = Map::new();
+
+            for (key, value) in &pairs {
+                let used = self.columns.iter().any(|column| match *column {
+                    Column::Key(ref k) => k == key,
+                    _ => false,
+                });
+
+                if !used && !obj.contains_key(key) {
+                    obj.insert(key.clone(), value.clone().into());
+                }
+            }
+
+            // serializing a map of json values never fails
+            serde_json::to_string(&Value::Object(obj)).unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        if self.rest {
+            fields.push(&rest);
+        }
+
+        let mut line = String::new();
+        row(&mut line, self.delimiter, fields.into_iter());
+        line
+    }
+}
+
+impl Drain for Csv {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let line = self.format(record, values);
+
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        output.write_all(line.as_bytes())?;
+        output.flush()
+    }
+}
+
+
+/// Appends a row consisting of the specified fields, terminated by CRLF.
+fn row<'a, I>(line: &mut String, delimiter: char, fields: I)
+where
+    I: Iterator<Item = &'a str>,
+{
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            line.push(delimiter);
+        }
+
+        let quote = field
+            .chars()
+            .any(|c| c == delimiter || c == '"' || c == '\r' || c == '\n');
+
+        if quote {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(field);
+        }
+    }
+
+    line.push_str("\r\n");
+}
@@ -0,0 +1,185 @@
+//! Configuration for a `fallback`-type logger and corresponding factories for
+//! an `Async` drain and a synchronous drain.
+//!
+//! A logger of this type passes all records to its primary logger. If the
+//! primary logger fails with an I/O error (e.g. because the disk is full), all
+//! records are diverted to the secondary logger instead. A warning containing
+//! the error is emitted to the secondary logger once the primary logger has
+//! failed. The primary logger is retried periodically and used again as soon
+//! as it succeeds.
+//!
+//! If the secondary logger fails as well, the record is discarded. Discarded
+//! records are counted and reported to the secondary logger once it succeeds
+//! again (see [`Discarding`](::drain::Discarding)), thus this logger never
+//! fails.
+//!
+//! The default factories build the drains of the nested configurations within
+//! the context they are invoked in, by default via the default drain factories
+//! (see [`Context`](::Context)). The `Async` factory wraps the combined drain
+//! in a single `Async` drain, thus only one `AsyncGuard` has to be kept alive.
+
+use {BoxDrain, Context, Error};
+use drain::Discarding;
+pub use common::{AsyncWorker, Duration};
+
+use std;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use slog::{Drain, Level, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+
+/// Configuration for a logger of type `fallback`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The configuration of the logger used as long as it does not fail.
+    pub primary: Box<::Config>,
+
+    /// The configuration of the logger used while the primary logger fails.
+    pub secondary: Box<::Config>,
+
+    /// The interval after which a failed primary logger should be retried.
+    #[serde(default = "default::retry")]
+    pub retry: Duration,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "fallback"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `fallback`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `fallback`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let primary = ctx.build(cfg.primary.as_ref())?;
+    let secondary = Discarding::new(ctx.build(cfg.secondary.as_ref())?);
+
+    Ok(Box::new(Fallback {
+        primary,
+        secondary,
+        retry: cfg.retry.into(),
+        failed: Mutex::new(None),
+    }))
+}
+
+
+/// A `Drain` diverting records to a secondary drain while the primary drain
+/// fails.
+///
+/// Errors of the secondary drain are handled by discarding the record, thus
+/// this drain never fails.
+struct Fallback {
+    primary:   BoxDrain,
+    secondary: Discarding<BoxDrain>,
+    retry:     std::time::Duration,
+
+    /// The time of the last failure of the primary drain, `None` if the
+    /// primary drain is in use.
+    failed: Mutex<Option<Instant>>,
+}
+
+impl Fallback {
+    fn warn(&self, error: &std::io::Error) {
+        let error = error.to_string();
+        let values = OwnedKVList::from(o!());
+
+        let _ = self.secondary.log(
+            &record!(
+                Level::Warning,
+                "",
+                &format_args!("primary drain failed, diverting records to secondary drain"),
+                b!("error" => error)
+            ),
+            &values,
+        );
+    }
+}
+
+impl Drain for Fallback {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let mut failed = self.failed.lock().unwrap_or_else(|e| e.into_inner());
+
+        let retry = match *failed {
+            Some(time) => time.elapsed() >= self.retry,
+            None => true,
+        };
+
+        if retry {
+            match self.primary.log(record, values) {
+                Ok(()) => {
+                    *failed = None;
+                    return Ok(());
+                },
+                Err(e) => {
+                    let first = failed.is_none();
+                    *failed = Some(Instant::now());
+
+                    if first {
+                        self.warn(&e);
+                    }
+                },
+            }
+        }
+
+        let _ = self.secondary.log(record, values);
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.primary.is_enabled(level) || self.secondary.is_enabled(level)
+    }
+}
+
+
+mod default {
+    use super::Duration;
+
+    pub fn retry() -> Duration { Duration::from_secs(30) }
+}
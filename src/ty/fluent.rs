@@ -0,0 +1,510 @@
+//! Configuration for a `fluent`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type sends records to Fluentd or Fluent Bit via the
+//! forward protocol, either via TCP or via a unix socket. Each record is sent
+//! as `[tag, time, record]` entry (Message mode), or, if batching is enabled,
+//! as part of a batch of entries sharing the same tag (PackedForward mode).
+//!
+//! The time of each entry is encoded as `EventTime`, i.e. with nanosecond
+//! precision. The record of each entry is a map containing the message of the
+//! record (`msg`), its level (`level`), and all key-value pairs of the record
+//! and logger.
+//!
+//! If the connection fails or a write does not complete within five seconds,
+//! the logger reconnects with exponential backoff.
+//! Records (or batches) which cannot be sent, e.g. while waiting for the next
+//! connection attempt, are dropped. Once a connection has been established
+//! again, the number of dropped records is reported in a warning record.
+//! Thus, the drain never fails, even if the instance is not reachable when the
+//! drain is built.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use kv;
+use msgpack;
+#[cfg(unix)]
+use net::connect_unix;
+use net::{connect_tcp, Connection, Reconnect, CONNECT_TIMEOUT};
+pub use common::{AsyncWorker, Directives, Duration, Level};
+
+use std;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use serde::{Serialize, Serializer};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::Utc;
+
+
+/// Configuration for a logger of type `fluent`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The Fluentd or Fluent Bit instance to which records should be sent.
+    pub target: Target,
+
+    /// The fluent tag of all records.
+    ///
+    /// Defaults to the tag of each record, or `slog` if a record has no tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    /// The maximum number of records sent in a single batch. A value greater
+    /// than one enables PackedForward mode.
+    #[serde(default = "default::batch")]
+    pub batch: usize,
+
+    /// The interval after which an incomplete batch is sent.
+    #[serde(default = "default::flush", rename = "flush-interval")]
+    pub flush_interval: Duration,
+
+    /// Whether to request and wait for an acknowledgement of each message.
+    #[serde(default)]
+    pub ack: bool,
+
+    /// The maximum time to wait for an acknowledgement.
+    #[serde(default = "default::ack_timeout", rename = "ack-timeout")]
+    pub ack_timeout: Duration,
+
+    /// The maximum delay between two connection attempts.
+    #[serde(default = "default::backoff")]
+    pub backoff: Duration,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "fluent"
+    }
+}
+
+
+/// The Fluentd or Fluent Bit instance to which records should be sent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// A TCP endpoint specified as `host:port`.
+    Tcp(String),
+
+    /// A unix stream socket at the specified path.
+    Unix(PathBuf),
+}
+
+impl Serialize for Target {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let (name, value) = match *self {
+            Target::Tcp(ref addr) => ("tcp", addr.as_str().into()),
+            Target::Unix(ref path) => ("unix", path.to_string_lossy()),
+        };
+
+        let mut state = serializer.serialize_struct("Target", 1)?;
+        state.serialize_field(name, &value)?;
+        state.end()
+    }
+}
+
+
+/// Factory for an `Async` drain of type `fluent`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `fluent`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.batch == 0 {
+        return Err(Error::msg("fluent batch size must be at least one"));
+    }
+
+    let transport = Transport {
+        target:      cfg.target.clone(),
+        stream:      None,
+        ack_timeout: cfg.ack_timeout.into(),
+    };
+
+    // connects with the first message, records are dropped until the instance
+    // is reachable
+    let state = Arc::new(Mutex::new(State {
+        connection: Connection::new(transport, cfg.backoff.into()),
+        batch: Batch::default(),
+    }));
+
+    if cfg.batch > 1 {
+        spawn_flusher(Arc::downgrade(&state), cfg.flush_interval.into());
+    }
+
+    let drain = Fluent {
+        state,
+        tag: cfg.tag.clone(),
+        batch: cfg.batch,
+        ack: cfg.ack,
+        counter: AtomicUsize::new(0),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+/// Periodically sends incomplete batches until the drain has been dropped.
+fn spawn_flusher(state: Weak<Mutex<State>>, interval: std::time::Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        match state.upgrade() {
+            Some(state) => {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                state.flush();
+            },
+            None => break,
+        }
+    });
+}
+
+
+mod default {
+    use super::Duration;
+
+    pub fn batch() -> usize { 1 }
+    pub fn flush() -> Duration { Duration::from_secs(1) }
+    pub fn ack_timeout() -> Duration { Duration::from_secs(30) }
+    pub fn backoff() -> Duration { Duration::from_secs(30) }
+}
+
+
+/// A `Drain` sending records via the forward protocol.
+struct Fluent {
+    state:   Arc<Mutex<State>>,
+    tag:     Option<String>,
+    batch:   usize,
+    ack:     bool,
+    counter: AtomicUsize,
+}
+
+impl Fluent {
+    fn tag<'a>(&'a self, record: &'a Record) -> &'a str {
+        match self.tag {
+            Some(ref tag) => tag,
+            None if !record.tag().is_empty() => record.tag(),
+            None => "slog",
+        }
+    }
+
+    /// Returns a new unique chunk ID.
+    fn chunk(&self) -> Option<String> {
+        if !self.ack {
+            return None;
+        }
+
+        let now = Utc::now();
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+
+        Some(format!("{:x}{:08x}{:x}", now.timestamp(), now.timestamp_subsec_nanos(), count))
+    }
+
+    /// Returns the specified record as Message mode message and its chunk ID.
+    fn message(&self, record: &Record, values: &OwnedKVList) -> (Vec<u8>, Option<String>) {
+        let mut msg = Vec::new();
+        let chunk = self.chunk();
+
+        msgpack::write_array_len(&mut msg, if chunk.is_some() { 4 } else { 3 });
+        msgpack::write_str(&mut msg, self.tag(record));
+        entry(&mut msg, record, values);
+
+        if let Some(ref chunk) = chunk {
+            msgpack::write_map_len(&mut msg, 1);
+            msgpack::write_str(&mut msg, "chunk");
+            msgpack::write_str(&mut msg, chunk);
+        }
+
+        (msg, chunk)
+    }
+}
+
+impl Drain for Fluent {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let tag = self.tag(record);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        state.connection.report(|transport, count, reason| {
+            let (msg, chunk) = report_dropped(count, reason, |record, values| {
+                self.message(record, values)
+            });
+
+            transport.send(&msg, chunk.as_deref())
+        });
+
+        if self.batch == 1 {
+            let (msg, chunk) = self.message(record, values);
+            state.send(&msg, chunk.as_deref(), 1);
+
+            return Ok(());
+        }
+
+        if state.batch.tag != tag && state.batch.count > 0 {
+            state.flush();
+        }
+
+        if state.batch.count == 0 {
+            state.batch.tag = tag.to_owned();
+            state.batch.chunk = self.chunk();
+        }
+
+        entry(&mut state.batch.entries, record, values);
+        state.batch.count += 1;
+
+        if state.batch.count >= self.batch {
+            state.flush();
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Fluent {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.flush();
+    }
+}
+
+/// Appends a `[time, record]` entry.
+fn entry(buf: &mut Vec<u8>, record: &Record, values: &OwnedKVList) {
+    let now = Utc::now();
+
+    let mut time = [0; 8];
+    time[..4].copy_from_slice(&(now.timestamp() as u32).to_be_bytes());
+    time[4..].copy_from_slice(&now.timestamp_subsec_nanos().to_be_bytes());
+
+    // pairs of the record take precedence over pairs of the logger
+    let mut pairs = vec![
+        ("msg".to_owned(), record.msg().to_string()),
+        ("level".to_owned(), record.level().as_short_str().to_owned()),
+    ];
+
+    for (key, value) in kv::collect(record, values) {
+        if !pairs.iter().any(|(k, _)| *k == key) {
+            pairs.push((key, value));
+        }
+    }
+
+    msgpack::write_array_len(buf, 2);
+    msgpack::write_ext(buf, 0, &time);
+    msgpack::write_map_len(buf, pairs.len());
+
+    for (key, value) in pairs {
+        msgpack::write_str(buf, &key);
+        msgpack::write_str(buf, &value);
+    }
+}
+
+
+struct State {
+    connection: Connection<Transport>,
+    batch:      Batch,
+}
+
+impl State {
+    /// Sends the specified message containing `count` records, counting them
+    /// as dropped if it cannot be sent.
+    fn send(&mut self, msg: &[u8], chunk: Option<&str>, count: usize) {
+        if let Err(e) = self.connection.send(|transport| transport.send(msg, chunk)) {
+            self.connection.discard(count as u64, &e);
+        }
+    }
+
+    /// Sends the current batch in PackedForward mode, if it is not empty.
+    fn flush(&mut self) {
+        if self.batch.count == 0 {
+            return;
+        }
+
+        let batch = std::mem::take(&mut self.batch);
+        let mut msg = Vec::with_capacity(batch.entries.len() + 64);
+
+        msgpack::write_array_len(&mut msg, 3);
+        msgpack::write_str(&mut msg, &batch.tag);
+        msgpack::write_bin(&mut msg, &batch.entries);
+        msgpack::write_map_len(&mut msg, if batch.chunk.is_some() { 2 } else { 1 });
+        msgpack::write_str(&mut msg, "size");
+        msgpack::write_uint(&mut msg, batch.count as u64);
+
+        if let Some(ref chunk) = batch.chunk {
+            msgpack::write_str(&mut msg, "chunk");
+            msgpack::write_str(&mut msg, chunk);
+        }
+
+        self.send(&msg, batch.chunk.as_deref(), batch.count);
+    }
+}
+
+
+/// Entries collected for PackedForward mode.
+#[derive(Default)]
+struct Batch {
+    tag:     String,
+    chunk:   Option<String>,
+    entries: Vec<u8>,
+    count:   usize,
+}
+
+
+/// The connection to a Fluentd or Fluent Bit instance.
+struct Transport {
+    target:      Target,
+    stream:      Option<Stream>,
+    ack_timeout: std::time::Duration,
+}
+
+impl Transport {
+    /// Sends the specified message via the current connection and waits for
+    /// its acknowledgement if a chunk ID is given.
+    fn send(&mut self, msg: &[u8], chunk: Option<&str>) -> io::Result<()> {
+        let stream = match self.stream {
+            Some(ref mut stream) => stream,
+            None => return Err(io::Error::new(io::ErrorKind::NotConnected, "not connected")),
+        };
+
+        stream.write_all(msg)?;
+        stream.flush()?;
+
+        if let Some(chunk) = chunk {
+            let response = msgpack::read(stream)?;
+
+            if response.get("ack").and_then(|v| v.as_str()) != Some(chunk) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid fluent ack"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Reconnect for Transport {
+    fn reconnect(&mut self) -> io::Result<()> {
+        self.stream = None;
+        self.stream = Some(Stream::connect(&self.target, self.ack_timeout)?);
+        Ok(())
+    }
+}
+
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(target: &Target, timeout: std::time::Duration) -> io::Result<Self> {
+        match *target {
+            Target::Tcp(ref addr) => {
+                let stream = connect_tcp(addr, CONNECT_TIMEOUT)?;
+                stream.set_read_timeout(Some(timeout))?;
+                Ok(Stream::Tcp(stream))
+            },
+            #[cfg(unix)]
+            Target::Unix(ref path) => {
+                let stream = connect_unix(path)?;
+                stream.set_read_timeout(Some(timeout))?;
+                Ok(Stream::Unix(stream))
+            },
+            #[cfg(not(unix))]
+            Target::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "unix sockets are not supported on this platform",
+            )),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.flush(),
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Configuration for a `gelf`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type sends each record as GELF 1.1 message (e.g. to
+//! Graylog), either via UDP or via TCP. Each message consists of the following
+//! fields:
+//!
+//! - `version`: always `"1.1"`.
+//! - `host`: the configured hostname.
+//! - `short_message`: the first line of the message of the record.
+//! - `full_message`: the full message of the record, if it spans multiple
+//!   lines.
+//! - `timestamp`: the current time in seconds since the UNIX epoch.
+//! - `level`: the syslog severity of the record, see
+//!   [`Level::severity`](::common::Level::severity).
+//! - `_module`, `_file`, `_line`: the location of the record.
+//! - `_tag`: the tag of the record, if it is not empty.
+//! - the key-value pairs of the record and logger as additional fields, i.e.
+//!   prefixed by `_` and with all characters other than alphanumerics, `_`,
+//!   `.`, and `-` replaced by `_`. Keys which would clash with the fields
+//!   above or with the reserved `_id` field (i.e. `id`, `module`, `file`,
+//!   `line`, and `tag`) are prefixed by `__` instead, e.g. `__line`.
+//!
+//! Messages sent via UDP are split into chunks if they exceed the configured
+//! chunk size and can optionally be compressed with gzip. Messages sent via
+//! TCP are terminated by a null byte.
+//!
+//! If a message cannot be sent, it is dropped and further attempts (including
+//! a new connection for TCP) are made with an increasing delay, up to the
+//! configured backoff. Messages exceeding 128 chunks are dropped as well. Once
+//! sending succeeds again, the number of dropped messages is reported in a
+//! warning message. Thus, the drain never fails after it has been built.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use host;
+use kv;
+use net::{connect_tcp, Connection, Reconnect, CONNECT_TIMEOUT};
+pub use common::{AsyncWorker, Directives, Duration, Level, Size};
+
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::Utc;
+
+
+/// Configuration for a logger of type `gelf`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The endpoint to which messages should be sent.
+    pub target: Target,
+
+    /// Whether messages sent via UDP should be compressed with gzip.
+    ///
+    /// Requires the `gzip` feature.
+    #[serde(default)]
+    pub compress: bool,
+
+    /// The maximum size of a single UDP datagram, messages exceeding this size
+    /// are split into chunks.
+    #[serde(default = "default::chunk_size", rename = "chunk-size")]
+    pub chunk_size: Size,
+
+    /// The hostname included in the messages.
+    ///
+    /// Defaults to the name of the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// The maximum delay between two attempts to send a message (and to
+    /// connect via TCP) after a failure.
+    #[serde(default = "default::backoff")]
+    pub backoff: Duration,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "gelf"
+    }
+}
+
+
+/// The endpoint to which GELF messages should be sent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// A UDP endpoint specified as `host:port`.
+    Udp(String),
+
+    /// A TCP endpoint specified as `host:port`.
+    Tcp(String),
+}
+
+impl Serialize for Target {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let (name, addr) = match *self {
+            Target::Udp(ref addr) => ("udp", addr),
+            Target::Tcp(ref addr) => ("tcp", addr),
+        };
+
+        let mut state = serializer.serialize_struct("Target", 1)?;
+        state.serialize_field(name, addr)?;
+        state.end()
+    }
+}
+
+
+/// Factory for an `Async` drain of type `gelf`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `gelf`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.compress && !cfg!(feature = "gzip") {
+        return Err(Error::msg("gelf compression requires the `gzip` feature"));
+    }
+
+    // a chunk needs room for its 12 byte header
+    if cfg.chunk_size.bytes() <= 12 {
+        return Err(Error::msg("gelf chunk size must be larger than 12 bytes"));
+    }
+
+    let connection = Connection::new(Transport::open(&cfg.target)?, cfg.backoff.into());
+
+    let drain = Gelf {
+        connection: Mutex::new(connection),
+        compress:   cfg.compress,
+        chunk_size: cfg.chunk_size.bytes() as usize,
+        hostname:   cfg.hostname.clone().or_else(host::hostname).unwrap_or_default(),
+        counter:    AtomicUsize::new(0),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    use common::{Duration, Size};
+
+    pub fn chunk_size() -> Size { Size(1420) }
+    pub fn backoff() -> Duration { Duration::from_secs(30) }
+}
+
+
+/// A `Drain` sending records as GELF messages.
+struct Gelf {
+    connection: Mutex<Connection<Transport>>,
+    compress:   bool,
+    chunk_size: usize,
+    hostname:   String,
+    counter:    AtomicUsize,
+}
+
+impl Gelf {
+    fn format(&self, record: &Record, values: &OwnedKVList) -> Vec<u8> {
+        let now = Utc::now();
+        let timestamp = now.timestamp() as f64 + f64::from(now.timestamp_subsec_millis()) / 1000.0;
+
+        let msg = record.msg().to_string();
+        let short = msg.lines().next().unwrap_or("");
+
+        let mut obj = Map::new();
+        obj.insert("version".into(), "1.1".into());
+        obj.insert("host".into(), self.hostname.clone().into());
+        obj.insert("short_message".into(), short.into());
+        if short.len() != msg.len() {
+            obj.insert("full_message".into(), msg.clone().into());
+        }
+        obj.insert("timestamp".into(), timestamp.into());
+        obj.insert("level".into(), Level::from(record.level()).severity().into());
+        obj.insert("_module".into(), record.module().into());
+        obj.insert("_file".into(), record.file().into());
+        obj.insert("_line".into(), record.line().into());
+
+        if !record.tag().is_empty() {
+            obj.insert("_tag".into(), record.tag().into());
+        }
+
+        // pairs of the record take precedence over pairs of the logger
+        for (key, value) in kv::collect(record, values) {
+            let name = field_name(&key);
+
+            if !obj.contains_key(&name) {
+                obj.insert(name, value.into());
+            }
+        }
+
+        // serializing a map of json values never fails
+        serde_json::to_vec(&Value::Object(obj)).unwrap_or_default()
+    }
+
+    /// Returns a new message ID for chunked messages.
+    fn message_id(&self) -> [u8; 8] {
+        let now = Utc::now();
+        let count = self.counter.fetch_add(1, Ordering::Relaxed) as u64;
+        let time = (now.timestamp() as u64) ^ (u64::from(now.timestamp_subsec_nanos()) << 32);
+
+        (time ^ count.rotate_left(17) ^ host::pid().rotate_left(43)).to_be_bytes()
+    }
+}
+
+impl Gelf {
+    /// Splits the specified message into the packets sent via the transport,
+    /// i.e. into (compressed) chunks for UDP and a single null-terminated frame
+    /// for TCP.
+    fn packets(&self, msg: Vec<u8>, transport: &Transport) -> io::Result<Vec<Vec<u8>>> {
+        if let Transport::Tcp(..) = *transport {
+            let mut frame = msg;
+            frame.push(0);
+
+            return Ok(vec![frame]);
+        }
+
+        let msg = if self.compress { compress(&msg)? } else { msg };
+
+        if msg.len() <= self.chunk_size {
+            return Ok(vec![msg]);
+        }
+
+        let chunks = msg.chunks(self.chunk_size - 12);
+        let count = chunks.len();
+
+        if count > 128 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "gelf message exceeds 128 chunks",
+            ));
+        }
+
+        let id = self.message_id();
+        let datagrams = chunks.enumerate().map(|(seq, chunk)| {
+            let mut datagram = Vec::with_capacity(chunk.len() + 12);
+            datagram.extend_from_slice(&[0x1e, 0x0f]);
+            datagram.extend_from_slice(&id);
+            datagram.push(seq as u8);
+            datagram.push(count as u8);
+            datagram.extend_from_slice(chunk);
+            datagram
+        });
+
+        Ok(datagrams.collect())
+    }
+}
+
+impl Drain for Gelf {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        connection.report(|transport, count, reason| {
+            let packets = report_dropped(count, reason, |record, values| {
+                self.packets(self.format(record, values), transport)
+            })?;
+
+            transport.send(&packets)
+        });
+
+        let msg = self.format(record, values);
+        let sent = self
+            .packets(msg, connection.transport())
+            .and_then(|packets| connection.send(|transport| transport.send(&packets)));
+
+        if let Err(e) = sent {
+            connection.discard(1, &e);
+        }
+
+        Ok(())
+    }
+}
+
+
+/// The connection to a GELF endpoint.
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(String, Option<TcpStream>),
+}
+
+impl Transport {
+    fn open(target: &Target) -> Result<Self, Error> {
+        match *target {
+            Target::Udp(ref addr) => {
+                let addr = addr
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| Error::msg("failed to resolve gelf address"))?;
+
+                let socket = if addr.is_ipv4() {
+                    UdpSocket::bind("0.0.0.0:0")?
+                } else {
+                    UdpSocket::bind("[::]:0")?
+                };
+
+                socket.connect(addr)?;
+                Ok(Transport::Udp(socket))
+            },
+            Target::Tcp(ref addr) => {
+                let stream = connect_tcp(addr, CONNECT_TIMEOUT)?;
+                Ok(Transport::Tcp(addr.clone(), Some(stream)))
+            },
+        }
+    }
+
+    /// Sends the specified packets via the current connection.
+    fn send(&mut self, packets: &[Vec<u8>]) -> io::Result<()> {
+        match *self {
+            Transport::Udp(ref socket) => {
+                for datagram in packets {
+                    socket.send(datagram)?;
+                }
+
+                Ok(())
+            },
+            Transport::Tcp(_, ref mut stream) => {
+                let result = match *stream {
+                    Some(ref mut s) => packets.iter().try_for_each(|frame| s.write_all(frame)),
+                    None => Err(io::Error::new(io::ErrorKind::NotConnected, "not connected")),
+                };
+
+                // a partially written frame cannot be continued
+                if result.is_err() {
+                    *stream = None;
+                }
+
+                result
+            },
+        }
+    }
+}
+
+impl Reconnect for Transport {
+    /// Re-establishes the connection. UDP sockets are connectionless and kept.
+    fn reconnect(&mut self) -> io::Result<()> {
+        if let Transport::Tcp(ref addr, ref mut stream) = *self {
+            *stream = Some(connect_tcp(addr, CONNECT_TIMEOUT)?);
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Converts a key to the name of an additional field. Keys clashing with the
+/// fields set by the drain or with the reserved `_id` field get an additional
+/// `_` prefix.
+fn field_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 2);
+    name.push('_');
+
+    if let "id" | "module" | "file" | "line" | "tag" = key {
+        name.push('_');
+    }
+
+    for c in key.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+            name.push(c);
+        } else {
+            name.push('_');
+        }
+    }
+
+    name
+}
+
+#[cfg(feature = "gzip")]
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    use flate2::write::GzEncoder;
+
+    let mut encoder = GzEncoder::new(Vec::new(), ::flate2::Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    Ok(data.to_vec())
+}
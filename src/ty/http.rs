@@ -0,0 +1,723 @@
+//! Configuration for an `http`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type collects records in a bounded in-memory queue, from
+//! which a background thread sends them in batches via HTTP `POST` requests.
+//! A batch is sent as soon as it reaches the configured number of records or
+//! size, or once the flush interval has elapsed. Failed requests are retried
+//! with exponential backoff.
+//!
+//! Records are dropped while the queue is full, as are batches which could not
+//! be sent after all retries. The number of dropped records is reported in a
+//! warning record at the start of the next batch. Thus, the drain never fails.
+//!
+//! When the drain is dropped, the remaining records are sent without retrying
+//! failed requests. Once a request fails, all records left are dropped, so
+//! that an unavailable server cannot delay shutdown indefinitely.
+//!
+//! Each record is represented as JSON object containing its timestamp (`ts`,
+//! RFC-3339 formatted), message (`msg`), level (`level`), and all key-value
+//! pairs of the record and logger. The body of each request depends on the
+//! configured [`Format`](Format).
+//!
+//! Only plain `http://` URLs are supported.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use kv;
+pub use common::{AsyncWorker, Directives, Duration, Level, Size};
+
+use std;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use serde_json::{self, Map, Value};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::{DateTime, Utc};
+
+
+/// Configuration for a logger of type `http`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The URL to which batches should be posted.
+    pub url: String,
+
+    /// The format of the request bodies.
+    #[serde(default)]
+    pub format: Format,
+
+    /// The Elasticsearch index to which records should be added.
+    ///
+    /// If not specified, the index must be part of the URL. Only used with the
+    /// [`Elasticsearch`](Format::Elasticsearch) format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+
+    /// The keys of the record (including `level`) whose values should be used
+    /// as Loki labels. Only used with the [`Loki`](Format::Loki) format.
+    ///
+    /// Records without any of these keys are labeled by their level, as Loki
+    /// rejects streams without labels.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+
+    /// Additional headers sent with each request, e.g. for authorization.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// The maximum number of records in a single batch.
+    #[serde(default = "default::batch_size", rename = "batch-size")]
+    pub batch_size: usize,
+
+    /// The maximum size of the encoded records in a single batch.
+    #[serde(default = "default::batch_bytes", rename = "batch-bytes")]
+    pub batch_bytes: Size,
+
+    /// The interval after which an incomplete batch is sent.
+    #[serde(default = "default::flush", rename = "flush-interval")]
+    pub flush_interval: Duration,
+
+    /// The maximum number of records waiting to be sent.
+    #[serde(default = "default::queue")]
+    pub queue: usize,
+
+    /// The maximum number of retries of a failed request.
+    #[serde(default = "default::retries")]
+    pub retries: usize,
+
+    /// The delay before the first retry, doubled for each further retry.
+    #[serde(default = "default::backoff")]
+    pub backoff: Duration,
+
+    /// The timeout for connecting to the server and for each read and write.
+    #[serde(default = "default::timeout")]
+    pub timeout: Duration,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "http"
+    }
+}
+
+
+/// The format of the request bodies.
+///
+/// Defaults to [`Ndjson`](Format::Ndjson).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Newline-delimited JSON, one record per line.
+    Ndjson,
+
+    /// The Elasticsearch `_bulk` API format, i.e. each record preceded by an
+    /// `index` action.
+    Elasticsearch,
+
+    /// The Grafana Loki push API format.
+    ///
+    /// Records are grouped into streams by the values of their labels, each
+    /// record is sent as JSON-encoded log line without its timestamp.
+    Loki,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Ndjson
+    }
+}
+
+
+/// Factory for an `Async` drain of type `http`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `http`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.batch_size == 0 || cfg.queue == 0 {
+        return Err(Error::msg("http batch size and queue must be at least one"));
+    }
+
+    let client = Client {
+        url:     Url::parse(&cfg.url)?,
+        headers: cfg.headers.clone(),
+        timeout: cfg.timeout.into(),
+    };
+
+    let worker = Worker {
+        client,
+        format:      cfg.format,
+        index:       cfg.index.clone(),
+        labels:      cfg.labels.clone(),
+        batch_size:  cfg.batch_size,
+        batch_bytes: cfg.batch_bytes.bytes() as usize,
+        interval:    cfg.flush_interval.into(),
+        retries:     cfg.retries,
+        backoff:     cfg.backoff.into(),
+    };
+
+    let shared = Arc::new(Shared {
+        queue:    Mutex::new(Queue::default()),
+        signal:   Condvar::new(),
+        capacity: cfg.queue,
+    });
+
+    let thread = {
+        let shared = shared.clone();
+        thread::spawn(move || worker.run(&shared))
+    };
+
+    let drain = Http {
+        shared,
+        thread: Some(thread),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    use super::{Duration, Size};
+
+    pub fn batch_size() -> usize { 100 }
+    pub fn batch_bytes() -> Size { Size(1024 * 1024) }
+    pub fn flush() -> Duration { Duration::from_secs(1) }
+    pub fn queue() -> usize { 10_000 }
+    pub fn retries() -> usize { 5 }
+    pub fn backoff() -> Duration { Duration::from_millis(500) }
+    pub fn timeout() -> Duration { Duration::from_secs(10) }
+}
+
+
+/// A `Drain` queueing records for the background thread sending them.
+struct Http {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drain for Http {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let entry = Entry::new(record, values);
+        let mut queue = self.shared.lock();
+
+        if queue.entries.len() >= self.shared.capacity {
+            queue.discard(1, "http queue is full");
+            return Ok(());
+        }
+
+        queue.entries.push_back(entry);
+        self.shared.signal.notify_one();
+
+        Ok(())
+    }
+}
+
+impl Drop for Http {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.signal.notify_one();
+
+        // the worker sends the remaining records, without retries, before exiting
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+struct Shared {
+    queue:    Mutex<Queue>,
+    signal:   Condvar,
+    capacity: usize,
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits for the specified time or until the drain has been dropped,
+    /// returning `false` in the latter case.
+    fn sleep(&self, duration: std::time::Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut queue = self.lock();
+
+        while !queue.closed {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return true;
+            }
+
+            queue = self
+                .signal
+                .wait_timeout(queue, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        false
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    entries: VecDeque<Entry>,
+    closed:  bool,
+    dropped: u64,
+    reason:  String,
+}
+
+impl Queue {
+    /// Counts the specified number of records as dropped.
+    fn discard(&mut self, count: u64, reason: &str) {
+        self.dropped += count;
+        self.reason = reason.to_owned();
+    }
+}
+
+
+/// Records taken from the queue, starting with a report of the records dropped
+/// before, if any.
+struct Batch {
+    entries:  Vec<Entry>,
+    reported: u64,
+}
+
+impl Batch {
+    /// Returns the number of records in this batch and reported by it.
+    fn records(&self) -> u64 {
+        match self.reported {
+            0 => self.entries.len() as u64,
+            n => n + self.entries.len() as u64 - 1,
+        }
+    }
+}
+
+
+/// A record waiting to be sent.
+struct Entry {
+    time:   DateTime<Utc>,
+    fields: Map<String, Value>,
+    size:   usize,
+}
+
+impl Entry {
+    fn new(record: &Record, values: &OwnedKVList) -> Self {
+        let mut fields = Map::new();
+        fields.insert("msg".into(), record.msg().to_string().into());
+        fields.insert("level".into(), record.level().as_short_str().into());
+
+        // pairs of the record take precedence over pairs of the logger
+        for (key, value) in kv::collect(record, values) {
+            fields.entry(key).or_insert_with(|| value.into());
+        }
+
+        let size = fields.iter().map(|(k, v)| k.len() + v.to_string().len() + 4).sum();
+
+        Entry {
+            time: Utc::now(),
+            fields,
+            size,
+        }
+    }
+
+    /// Returns the record as JSON object including its timestamp.
+    fn document(&self) -> Value {
+        let mut doc = Map::new();
+        doc.insert("ts".into(), self.time.to_rfc3339().into());
+        doc.extend(self.fields.clone());
+        Value::Object(doc)
+    }
+}
+
+
+/// The background thread sending batches.
+struct Worker {
+    client:      Client,
+    format:      Format,
+    index:       Option<String>,
+    labels:      Vec<String>,
+    batch_size:  usize,
+    batch_bytes: usize,
+    interval:    std::time::Duration,
+    retries:     usize,
+    backoff:     std::time::Duration,
+}
+
+impl Worker {
+    fn run(&self, shared: &Shared) {
+        loop {
+            let (batch, closed) = self.next_batch(shared);
+
+            if !batch.entries.is_empty() {
+                if let Err(reason) = self.send(&batch.entries, shared) {
+                    let mut queue = shared.lock();
+                    queue.discard(batch.records(), &reason);
+
+                    // nobody waits for the remaining records anymore
+                    if queue.closed {
+                        let remaining = queue.entries.len() as u64;
+                        queue.discard(remaining, &reason);
+                        queue.entries.clear();
+                        break;
+                    }
+                }
+            }
+
+            if closed && batch.entries.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Waits until a batch is complete, the flush interval has elapsed, or the
+    /// drain has been dropped, and takes the batch from the queue.
+    fn next_batch(&self, shared: &Shared) -> (Batch, bool) {
+        let deadline = Instant::now() + self.interval;
+        let mut queue = shared.lock();
+
+        loop {
+            let bytes: usize = queue.entries.iter().take(self.batch_size).map(|e| e.size).sum();
+            let full = queue.entries.len() >= self.batch_size || bytes >= self.batch_bytes;
+            let now = Instant::now();
+
+            if full || queue.closed || now >= deadline {
+                break;
+            }
+
+            queue = shared
+                .signal
+                .wait_timeout(queue, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        let mut batch = Vec::new();
+        let mut bytes = 0;
+
+        // once the drain has been dropped, a report is only sent along with
+        // the remaining records to avoid retrying it indefinitely
+        let reported = if queue.closed && queue.entries.is_empty() {
+            0
+        } else {
+            std::mem::take(&mut queue.dropped)
+        };
+
+        if reported > 0 {
+            batch.push(report_dropped(reported, &queue.reason, Entry::new));
+        }
+
+        while batch.len() < self.batch_size {
+            let size = match queue.entries.front() {
+                Some(entry) => entry.size,
+                None => break,
+            };
+
+            if !batch.is_empty() && bytes + size > self.batch_bytes {
+                break;
+            }
+
+            bytes += size;
+            batch.extend(queue.entries.pop_front());
+        }
+
+        let batch = Batch {
+            entries: batch,
+            reported,
+        };
+
+        (batch, queue.closed)
+    }
+
+    /// Sends a batch, retrying with exponential backoff until the drain has
+    /// been dropped. Returns the reason of the last failure if the batch could
+    /// not be sent.
+    fn send(&self, batch: &[Entry], shared: &Shared) -> Result<(), String> {
+        let (content_type, body) = self.body(batch);
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+
+        loop {
+            match self.client.post(content_type, &body) {
+                Ok(()) => return Ok(()),
+                Err(Failure::Transient(_)) if attempt < self.retries && shared.sleep(backoff) => {
+                    backoff *= 2;
+                    attempt += 1;
+                },
+                Err(Failure::Transient(reason)) | Err(Failure::Permanent(reason)) => {
+                    return Err(reason);
+                },
+            }
+        }
+    }
+
+    fn body(&self, batch: &[Entry]) -> (&'static str, Vec<u8>) {
+        let mut body = Vec::new();
+
+        // serializing json values to a vector never fails
+        match self.format {
+            Format::Ndjson => {
+                for entry in batch {
+                    let _ = serde_json::to_writer(&mut body, &entry.document());
+                    body.push(b'\n');
+                }
+
+                ("application/x-ndjson", body)
+            },
+            Format::Elasticsearch => {
+                let action = match self.index {
+                    Some(ref index) => json!({ "index": { "_index": index } }),
+                    None => json!({ "index": {} }),
+                };
+
+                for entry in batch {
+                    let _ = serde_json::to_writer(&mut body, &action);
+                    body.push(b'\n');
+                    let _ = serde_json::to_writer(&mut body, &entry.document());
+                    body.push(b'\n');
+                }
+
+                ("application/x-ndjson", body)
+            },
+            Format::Loki => {
+                let mut streams: Vec<(Map<String, Value>, Vec<Value>)> = Vec::new();
+
+                for entry in batch {
+                    let mut stream = Map::new();
+
+                    for key in &self.labels {
+                        if let Some(value) = entry.fields.get(key) {
+                            let value = match *value {
+                                Value::String(ref s) => s.clone(),
+                                ref other => other.to_string(),
+                            };
+
+                            stream.insert(label_name(key), value.into());
+                        }
+                    }
+
+                    if stream.is_empty() {
+                        if let Some(level) = entry.fields.get("level") {
+                            stream.insert("level".into(), level.clone());
+                        }
+                    }
+
+                    let time = entry.time.timestamp_nanos_opt().unwrap_or_default();
+                    let line = Value::Object(entry.fields.clone()).to_string();
+                    let value = json!([time.to_string(), line]);
+
+                    match streams.iter_mut().find(|s| s.0 == stream) {
+                        Some(s) => s.1.push(value),
+                        None => streams.push((stream, vec![value])),
+                    }
+                }
+
+                let streams: Vec<Value> = streams
+                    .into_iter()
+                    .map(|(stream, values)| json!({ "stream": stream, "values": values }))
+                    .collect();
+
+                let _ = serde_json::to_writer(&mut body, &json!({ "streams": streams }));
+
+                ("application/json", body)
+            },
+        }
+    }
+}
+
+/// Converts a key to a valid Loki label name.
+fn label_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+
+/// The reason of a failed request.
+enum Failure {
+    /// The request may succeed if retried, e.g. on connection failures or
+    /// server errors.
+    Transient(String),
+
+    /// The request will not succeed if retried, e.g. on client errors.
+    Permanent(String),
+}
+
+/// A minimal HTTP/1.1 client.
+struct Client {
+    url:     Url,
+    headers: BTreeMap<String, String>,
+    timeout: std::time::Duration,
+}
+
+impl Client {
+    fn post(&self, content_type: &str, body: &[u8]) -> Result<(), Failure> {
+        let status = match self.post_1(content_type, body) {
+            Ok(status) if (200..300).contains(&status) => return Ok(()),
+            Ok(status) => status,
+            Err(e) => return Err(Failure::Transient(e.to_string())),
+        };
+
+        let reason = format!("http status {}", status);
+
+        if status == 408 || status == 429 || status >= 500 {
+            Err(Failure::Transient(reason))
+        } else {
+            Err(Failure::Permanent(reason))
+        }
+    }
+
+    /// Sends a request and returns the status code of the response.
+    fn post_1(&self, content_type: &str, body: &[u8]) -> io::Result<u16> {
+        let host = self.url.host.trim_start_matches('[').trim_end_matches(']');
+        let addr = (host, self.url.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "failed to resolve host"))?;
+
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n",
+            self.url.path,
+            self.url.host,
+            self.url.port,
+            content_type,
+            body.len(),
+        );
+
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+
+        // e.g. "HTTP/1.1 204 No Content"
+        status
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid http response"))
+    }
+}
+
+
+/// The components of a `http://` URL.
+struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self, Error> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(Error::msg(&format!("unsupported url `{}`, expected http://", url))),
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, "/"),
+        };
+
+        let (host, port) = match authority.rfind(':') {
+            Some(pos) if !authority.ends_with(']') => {
+                let port = authority[pos + 1..]
+                    .parse()
+                    .map_err(|_| Error::msg(&format!("invalid port in url `{}`", url)))?;
+
+                (&authority[..pos], port)
+            },
+            _ => (authority, 80),
+        };
+
+        if host.is_empty() {
+            return Err(Error::msg(&format!("missing host in url `{}`", url)));
+        }
+
+        Ok(Url {
+            host: host.to_owned(),
+            port,
+            path: path.to_owned(),
+        })
+    }
+}
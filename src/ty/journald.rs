@@ -0,0 +1,355 @@
+//! Configuration for a `journald`-type logger and corresponding factories for
+//! an `Async` drain and a synchronous drain.
+//!
+//! A logger of this type sends each record as entry to systemd-journald via
+//! its native protocol, i.e. as datagram to the journal socket. Entries
+//! exceeding the maximum datagram size are written to a sealed memory file,
+//! which is then passed to journald instead.
+//!
+//! Each entry consists of the following fields:
+//!
+//! - `MESSAGE`: the message of the record.
+//! - `PRIORITY`: the syslog severity of the record, see
+//!   [`Level::severity`](::common::Level::severity).
+//! - `SYSLOG_IDENTIFIER`: the configured identifier.
+//! - `CODE_FILE`, `CODE_LINE`, `CODE_MODULE`: the location of the record.
+//! - `TAG`: the tag of the record, if it is not empty.
+//! - the key-value pairs of the record and logger, with their keys converted
+//!   to valid journal field names (i.e. uppercase, with all characters other
+//!   than `A-Z`, `0-9`, and `_` replaced by `_`, and leading underscores
+//!   removed). Keys which would clash with the fields above (e.g. `message`
+//!   or `code_file`) are prefixed by `F_`, e.g. `F_MESSAGE`.
+//!
+//! If an entry cannot be sent, e.g. while journald is restarting, it is
+//! dropped. Once sending succeeds again, the number of dropped entries is
+//! reported in a warning entry. Thus, the drain never fails after it has been
+//! built.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use host;
+use kv;
+pub use common::{AsyncWorker, Directives, Level};
+
+use std;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use libc;
+
+
+/// Configuration for a logger of type `journald`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The path of the journal socket.
+    #[serde(default = "default::socket")]
+    pub socket: PathBuf,
+
+    /// The syslog identifier of the entries.
+    ///
+    /// Defaults to the file name of the current executable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            socket: default::socket(),
+            identifier: None,
+            level: Directives::default(),
+            handle: None,
+            worker: AsyncWorker::default(),
+        }
+    }
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "journald"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `journald`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `journald`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let drain = Journald {
+        socket:     UnixDatagram::unbound()?,
+        path:       cfg.socket.clone(),
+        identifier: cfg.identifier.clone().or_else(host::name).unwrap_or_default(),
+        dropped:    Mutex::new(Dropped::default()),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    use std::path::PathBuf;
+
+    pub fn socket() -> PathBuf { "/run/systemd/journal/socket".into() }
+}
+
+
+/// The fields set by the drain itself, which keys must not clash with.
+const RESERVED: &[&str] = &[
+    "MESSAGE",
+    "PRIORITY",
+    "SYSLOG_IDENTIFIER",
+    "CODE_FILE",
+    "CODE_LINE",
+    "CODE_MODULE",
+    "TAG",
+];
+
+
+/// A `Drain` sending records as entries to systemd-journald.
+struct Journald {
+    socket:     UnixDatagram,
+    path:       PathBuf,
+    identifier: String,
+    dropped:    Mutex<Dropped>,
+}
+
+/// The entries dropped since the last entry has been sent successfully.
+#[derive(Default)]
+struct Dropped {
+    count: u64,
+    error: String,
+}
+
+impl Journald {
+    fn format(&self, record: &Record, values: &OwnedKVList) -> Vec<u8> {
+        let mut entry = Vec::new();
+        let severity = Level::from(record.level()).severity();
+
+        field(&mut entry, "MESSAGE", &record.msg().to_string());
+        field(&mut entry, "PRIORITY", &severity.to_string());
+        field(&mut entry, "SYSLOG_IDENTIFIER", &self.identifier);
+        field(&mut entry, "CODE_FILE", record.file());
+        field(&mut entry, "CODE_LINE", &record.line().to_string());
+        field(&mut entry, "CODE_MODULE", record.module());
+
+        if !record.tag().is_empty() {
+            field(&mut entry, "TAG", record.tag());
+        }
+
+        for (key, value) in kv::collect(record, values) {
+            if let Some(name) = field_name(&key) {
+                field(&mut entry, &name, &value);
+            }
+        }
+
+        entry
+    }
+
+    fn send(&self, entry: &[u8]) -> io::Result<()> {
+        match self.socket.send_to(entry, &self.path) {
+            Ok(_) => Ok(()),
+            Err(ref e) if is_too_large(e) => send_memfd(&self.socket, &self.path, entry),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drain for Journald {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let mut dropped = self.dropped.lock().unwrap_or_else(|e| e.into_inner());
+
+        if dropped.count > 0 {
+            let report = report_dropped(dropped.count, &dropped.error, |record, values| {
+                self.format(record, values)
+            });
+
+            if self.send(&report).is_ok() {
+                dropped.count = 0;
+            }
+        }
+
+        if let Err(e) = self.send(&self.format(record, values)) {
+            dropped.count += 1;
+            dropped.error = e.to_string();
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Appends a field to the specified entry.
+///
+/// Values containing newlines are serialized in the binary format, prefixed
+/// by their length as little-endian 64-bit integer.
+fn field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+
+    if value.contains('\n') {
+        let len = value.len() as u64;
+
+        entry.push(b'\n');
+        entry.extend_from_slice(&len.to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+/// Converts a key to a valid journal field name. Names clashing with the
+/// fields set by the drain get an additional `F_` prefix.
+///
+/// Returns `None` if no valid name can be derived from the key.
+fn field_name(key: &str) -> Option<String> {
+    let mut name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .skip_while(|&c| c == '_')
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'F');
+    } else if RESERVED.contains(&name.as_str()) {
+        name.insert_str(0, "F_");
+    }
+
+    name.truncate(64);
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn is_too_large(err: &io::Error) -> bool {
+    let code = err.raw_os_error();
+    code == Some(libc::EMSGSIZE) || code == Some(libc::ENOBUFS)
+}
+
+
+/// Sends an entry by writing it to a sealed memory file and passing the file
+/// descriptor of said file to journald.
+fn send_memfd(socket: &UnixDatagram, path: &Path, entry: &[u8]) -> io::Result<()> {
+    let name = b"slog-conf-journald\0";
+    let fd = unsafe { libc::memfd_create(name.as_ptr() as *const _, libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // closes the descriptor once we are done
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(entry)?;
+
+    let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+    if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    send_fd(socket, path, fd)
+}
+
+/// Sends an empty datagram carrying the specified file descriptor to the
+/// socket at the given path.
+fn send_fd(socket: &UnixDatagram, path: &Path, fd: libc::c_int) -> io::Result<()> {
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "socket path too long"));
+    }
+
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+
+    let space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as _) } as usize;
+    let mut control = vec![0u8; space];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_name = &mut addr as *mut _ as *mut libc::c_void;
+    msg.msg_namelen = std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space as _;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as _) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, fd);
+
+        if libc::sendmsg(socket.as_raw_fd(), &msg, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,15 @@
-//! Configuration for a `json`-type logger and corresponding factory for an
-//! `Async` drain.
+//! Configuration for a `json`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
 //!
 //! Corresponds to a logger created with `slog_json::Json`.
 
-use Error;
-pub use common::{Level, OpenMode, Target, Timestamp};
+use {BoxDrain, Context, Error};
+use drain::{LevelFilter, Reporting};
+#[cfg(feature = "json-bunyan")]
+use host;
+pub use common::{AsyncWorker, Directives, Level, OpenMode, Rotation, Target, Timestamp};
 use common::OptionalTag;
+use output::Dropped;
 
 use std;
 
@@ -27,21 +31,36 @@ pub struct Config {
     #[serde(default)]
     pub format: Format,
 
-    /// The minimal logging level the logger should output.
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
     #[serde(default)]
-    pub level: Level,
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
 
     /// The timestamp format.
     #[serde(default)]
     pub timestamp: Timestamp,
 
-    /// If set to `true`, start each entry on a new line.
+    /// If set to `true`, start each entry on a new line. Must be `true` for
+    /// a network target, which sends each line as a single record.
     #[serde(default = "default::newlines")]
     pub newlines: bool,
 
-    /// If set to `true`, emit pretty-formatted json.
+    /// If set to `true`, emit pretty-formatted json. Must be `false` for a
+    /// network target, which sends each line as a single record.
     #[serde(default = "default::pretty")]
     pub pretty: bool,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
 }
 
 impl ::Config for Config {
@@ -99,33 +118,47 @@ impl ::Factory for Factory {
     type Target = (Async, AsyncGuard);
 
     fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
-        build(cfg)
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
     }
 }
 
-fn build(cfg: &Config) -> Result<(Async, AsyncGuard), Error> {
-    match cfg.target {
-        Target::Stdout => build_1(cfg, Json::new(std::io::stdout())),
-        Target::Stderr => build_1(cfg, Json::new(std::io::stderr())),
-        Target::File { ref path, mode } => {
-            let mut opt = std::fs::OpenOptions::new();
 
-            match mode {
-                OpenMode::Append => opt.create(true).write(true).append(true),
-                OpenMode::Truncate => opt.create(true).write(true).truncate(true),
-                OpenMode::New => opt.create_new(true).write(true),
-            };
+/// Factory for a synchronous drain of type `json`.
+pub struct DrainFactory;
 
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
 
-            build_1(cfg, Json::new(opt.open(path)?))
-        },
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if let Target::Network { .. } = cfg.target {
+        if !cfg.newlines || cfg.pretty {
+            return Err(Error::msg(
+                "a network target requires `newlines = true` and `pretty = false`",
+            ));
+        }
     }
+
+    let output = ctx.open(&cfg.target)?;
+    let dropped = output.dropped();
+
+    build_1(cfg, ctx, Json::new(output), dropped)
 }
 
-fn build_1<W>(cfg: &Config, builder: JsonBuilder<W>) -> Result<(Async, AsyncGuard), Error>
+fn build_1<W>(cfg: &Config, ctx: &Context, builder: JsonBuilder<W>, dropped: Dropped) -> Result<BoxDrain, Error>
 where
     W: std::io::Write + Send + 'static,
 {
@@ -175,18 +208,18 @@ where
             Timestamp::Rfc3339Utc => builder.add_key_value(o!(
                 "msg" => PushFnValue(|r, s| s.emit(r.msg())),
                 "level" => FnValue(|r| bunyan::level(r.level())),
-                "pid" => bunyan::pid(),
-                "name" => bunyan::name(),
-                "hostname" => bunyan::hostname(),
+                "pid" => host::pid(),
+                "name" => host::name().unwrap_or_else(|| "<unknown>".into()),
+                "hostname" => host::hostname().unwrap_or_else(|| "<unknown>".into()),
                 "time" => PushFnValue(timestamp_iso8601_utc),
                 "v" => 0u8,
             )),
             Timestamp::Rfc3339Local => builder.add_key_value(o!(
                 "msg" => PushFnValue(|r, s| s.emit(r.msg())),
                 "level" => FnValue(|r| bunyan::level(r.level())),
-                "pid" => bunyan::pid(),
-                "name" => bunyan::name(),
-                "hostname" => bunyan::hostname(),
+                "pid" => host::pid(),
+                "name" => host::name().unwrap_or_else(|| "<unknown>".into()),
+                "hostname" => host::hostname().unwrap_or_else(|| "<unknown>".into()),
                 "time" => PushFnValue(timestamp_iso8601_loc),
                 "v" => 0u8,
             )),
@@ -196,17 +229,22 @@ where
     let drain = builder
         .set_newlines(cfg.newlines)
         .set_pretty(cfg.pretty)
+        .set_flush(cfg.target.is_record_oriented())
         .build();
 
-    build_2(cfg, drain)
+    build_2(cfg, ctx, drain, dropped)
 }
 
-fn build_2<W>(cfg: &Config, drain: Json<W>) -> Result<(Async, AsyncGuard), Error>
+fn build_2<W>(cfg: &Config, ctx: &Context, drain: Json<W>, dropped: Dropped) -> Result<BoxDrain, Error>
 where
     W: std::io::Write + Send + 'static,
 {
-    let drain = drain.filter_level(cfg.level.into());
-    Ok(Async::new(drain.fuse()).build_with_guard())
+    let filter = LevelFilter::new(Reporting::new(drain, dropped), cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
 }
 
 
@@ -224,8 +262,8 @@ mod default {
     pub fn pretty() -> bool { false }
 }
 
+#[cfg(feature = "json-bunyan")]
 mod bunyan {
-    use std;
     use slog::Level;
 
     pub fn level(level: Level) -> u8 {
@@ -238,44 +276,4 @@ mod bunyan {
             Level::Trace => 10,
         }
     }
-
-    pub fn name() -> String {
-        let path = std::env::current_exe().ok();
-        let name = path.as_ref().and_then(|path| path.file_name());
-
-        name.and_then(|name| name.to_str())
-            .map(|name| name.to_owned())
-            .unwrap_or_else(|| "<unknown>".into())
-    }
-
-    pub fn hostname() -> String {
-        #[cfg(unix)]
-        use libc::gethostname;
-
-        #[cfg(windows)]
-        use winapi::um::winsock2::gethostname;
-
-        const MAXLEN: usize = 256;
-
-        let mut buf = [0 as std::os::raw::c_char; MAXLEN];
-        let err = unsafe { gethostname(buf.as_mut_ptr() as *mut _, MAXLEN as _) };
-
-        let name = if err == 0 {
-            unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()).to_str().ok() }
-        } else {
-            None
-        };
-
-        name.unwrap_or("<unknown>").to_owned()
-    }
-
-    pub fn pid() -> u64 {
-        #[cfg(unix)]
-        use libc::getpid;
-
-        #[cfg(windows)]
-        use winapi::um::processthreadsapi::GetCurrentProcessId as getpid;
-
-        unsafe { getpid() as u64 }
-    }
 }
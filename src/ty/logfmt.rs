@@ -0,0 +1,264 @@
+//! Configuration for a `logfmt`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type writes each record as a single line of
+//! space-separated `key=value` pairs, e.g.
+//!
+//! ```text
+//! ts=2018-07-01T12:00:00.000000000+00:00 level=info msg="server started" port=8080
+//! ```
+//!
+//! Each line starts with the timestamp, the level, and the message, followed
+//! by the tag of the record (if it is not empty), the key-value pairs of the
+//! record, and the key-value pairs of the logger. The names of the first three
+//! keys can be configured.
+//!
+//! Values are quoted if they are empty or contain spaces, `=`, quotes, or
+//! control characters. Within quoted values, quotes and backslashes are
+//! escaped with a backslash, newlines, carriage returns, and tabs are written
+//! as `\n`, `\r`, and `\t`, and other control characters as `\u{..}`. Keys
+//! cannot be quoted, thus all such characters are replaced by `_` in keys.
+
+use {BoxDrain, Context, Error};
+use drain::{LevelFilter, Reporting};
+use kv;
+use output::Opened;
+pub use common::{AsyncWorker, Directives, Level, OpenMode, Rotation, Target, Timestamp};
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::{Local, Utc};
+
+
+/// Configuration for a logger of type `logfmt`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The target to which the logger should write.
+    #[serde(default)]
+    pub target: Target,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The timestamp format.
+    #[serde(default)]
+    pub timestamp: Timestamp,
+
+    /// The key of the timestamp.
+    #[serde(default = "default::time_key", rename = "time-key")]
+    pub time_key: String,
+
+    /// The key of the level.
+    #[serde(default = "default::level_key", rename = "level-key")]
+    pub level_key: String,
+
+    /// The key of the message.
+    #[serde(default = "default::message_key", rename = "message-key")]
+    pub message_key: String,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            target: Target::default(),
+            level: Directives::default(),
+            handle: None,
+            timestamp: Timestamp::default(),
+            time_key: default::time_key(),
+            level_key: default::level_key(),
+            message_key: default::message_key(),
+            worker: AsyncWorker::default(),
+        }
+    }
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "logfmt"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `logfmt`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `logfmt`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let output = ctx.open(&cfg.target)?;
+    let dropped = output.dropped();
+
+    let drain = Logfmt {
+        output:      Mutex::new(output),
+        timestamp:   cfg.timestamp,
+        time_key:    key(&cfg.time_key),
+        level_key:   key(&cfg.level_key),
+        message_key: key(&cfg.message_key),
+    };
+
+    let filter = LevelFilter::new(Reporting::new(drain, dropped), cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    pub fn time_key() -> String { "ts".into() }
+    pub fn level_key() -> String { "level".into() }
+    pub fn message_key() -> String { "msg".into() }
+}
+
+
+/// A `Drain` writing records in logfmt.
+struct Logfmt {
+    output:      Mutex<Opened>,
+    timestamp:   Timestamp,
+    time_key:    String,
+    level_key:   String,
+    message_key: String,
+}
+
+impl Logfmt {
+    fn format(&self, record: &Record, values: &OwnedKVList) -> String {
+        let time = match self.timestamp {
+            Timestamp::Rfc3339Utc => Utc::now().to_rfc3339(),
+            Timestamp::Rfc3339Local => Local::now().to_rfc3339(),
+        };
+
+        let mut line = String::new();
+        pair(&mut line, &self.time_key, &time);
+        pair(&mut line, &self.level_key, Level::from(record.level()).to_str_lowercase());
+        pair(&mut line, &self.message_key, &record.msg().to_string());
+
+        if !record.tag().is_empty() {
+            pair(&mut line, "tag", record.tag());
+        }
+
+        for (k, v) in kv::collect(record, values) {
+            pair(&mut line, &key(&k), &v);
+        }
+
+        line.push('\n');
+        line
+    }
+}
+
+impl Drain for Logfmt {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let line = self.format(record, values);
+
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        output.write_all(line.as_bytes())?;
+        output.flush()
+    }
+}
+
+
+/// Appends a `key=value` pair, separated from preceding pairs by a space.
+fn pair(line: &mut String, key: &str, value: &str) {
+    if !line.is_empty() {
+        line.push(' ');
+    }
+
+    line.push_str(key);
+    line.push('=');
+
+    if !needs_quotes(value) {
+        line.push_str(value);
+        return;
+    }
+
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(line, "\\u{{{:x}}}", c as u32);
+            },
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control())
+}
+
+/// Replaces all characters that are not allowed in keys by `_`.
+fn key(name: &str) -> String {
+    if name.is_empty() {
+        return "_".into();
+    }
+
+    name.chars()
+        .map(|c| {
+            if c == ' ' || c == '=' || c == '"' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
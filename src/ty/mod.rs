@@ -11,3 +11,45 @@ pub mod term;
 
 #[cfg(feature = "json")]
 pub mod json;
+
+#[cfg(feature = "logfmt")]
+pub mod logfmt;
+
+#[cfg(feature = "csv")]
+pub mod csv;
+
+#[cfg(feature = "binary")]
+pub mod binary;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(feature = "multi")]
+pub mod multi;
+
+#[cfg(feature = "route")]
+pub mod route;
+
+#[cfg(feature = "fallback")]
+pub mod fallback;
+
+#[cfg(feature = "auto")]
+pub mod auto;
+
+#[cfg(feature = "spool")]
+pub mod spool;
+
+#[cfg(feature = "syslog")]
+pub mod syslog;
+
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub mod journald;
+
+#[cfg(feature = "gelf")]
+pub mod gelf;
+
+#[cfg(feature = "fluent")]
+pub mod fluent;
+
+#[cfg(feature = "http")]
+pub mod http;
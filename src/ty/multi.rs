@@ -0,0 +1,119 @@
+//! Configuration for a `multi`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type duplicates each record to all of its nested loggers.
+//! Each nested logger is described by its own configuration, including its own
+//! filter-level, and is deserialized via the default deserializer-registry.
+//!
+//! Errors of a nested logger do not affect the other nested loggers and are
+//! not passed on. Records a nested logger fails to log are discarded and
+//! reported to it once it succeeds again (see
+//! [`Discarding`](::drain::Discarding)).
+//!
+//! The default factories build the drains of the nested configurations within
+//! the context they are invoked in, by default via the default drain factories
+//! (see [`Context`](::Context)). The `Async` factory wraps the combined drain
+//! in a single `Async` drain, thus only one `AsyncGuard` has to be kept alive.
+
+use {BoxDrain, Context, Error};
+use drain::Discarding;
+pub use common::AsyncWorker;
+
+use std;
+
+use slog::{Drain, Level, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+
+/// Configuration for a logger of type `multi`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// The configurations of the nested loggers.
+    #[serde(default)]
+    pub drains: Vec<Box<::Config>>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "multi"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `multi`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `multi`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let drains = cfg.drains
+        .iter()
+        .map(|cfg| ctx.build(cfg.as_ref()).map(Discarding::new))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Box::new(Multi { drains }))
+}
+
+
+/// A `Drain` passing each record to all of its nested drains.
+///
+/// Records are passed to all nested drains, even if one of them fails. Errors
+/// are handled per nested drain, thus this drain never fails.
+struct Multi {
+    drains: Vec<Discarding<BoxDrain>>,
+}
+
+impl Drain for Multi {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        for drain in &self.drains {
+            let _ = drain.log(record, values);
+        }
+
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drains.iter().any(|drain| drain.is_enabled(level))
+    }
+}
@@ -1,18 +1,24 @@
-//! Configuration for a `null`-type logger and corresponding factory for an
-//! `Async` drain.
+//! Configuration for a `null`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
 //!
 //! Corresponds to a logger created with `slog_term::Discard`.
 //! This type of logger will not emit any output.
 
-use Error;
+use {BoxDrain, Error};
+pub use common::AsyncWorker;
 
-use slog::Discard;
+use slog::{Discard, Drain};
 use slog_async::{Async, AsyncGuard};
 
 
 /// Configuration for a logger of type `null`.
-#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
-pub struct Config;
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
 
 impl ::Config for Config {
     fn ty(&self) -> &'static str {
@@ -28,7 +34,20 @@ impl ::Factory for Factory {
     type Config = Config;
     type Target = (Async, AsyncGuard);
 
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(Discard).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `null`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
     fn build(&self, _cfg: &Config) -> Result<Self::Target, Error> {
-        Ok(Async::new(Discard).build_with_guard())
+        Ok(Box::new(Discard.map_err(|never| match never {})))
     }
 }
@@ -1,14 +1,17 @@
-//! Configuration for a `plain`-type logger and corresponding factory for an
-//! `Async` drain.
+//! Configuration for a `plain`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
 //!
 //! Corresponds to a logger created with `slog_term::PlainDecorator`.
 
-use Error;
-pub use common::{Level, OpenMode, Target, Timestamp};
+use {BoxDrain, Context, Error};
+use drain::{LevelFilter, Prioritize, Reporting};
+use output::Dropped;
+use output::journal::{self, Priority, PriorityPrefix, Stream};
+pub use common::{AsyncWorker, Directives, Journal, Level, OpenMode, Rotation, Target, Timestamp};
 
 use std;
 
-use slog::{Drain, Never};
+use slog::Drain;
 use slog_async::{Async, AsyncGuard};
 use slog_term::{CompactFormat, Decorator, FullFormat, PlainDecorator};
 
@@ -26,13 +29,30 @@ pub struct Config {
     #[serde(default)]
     pub format: Format,
 
-    /// The minimal logging level the logger should output.
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
     #[serde(default)]
-    pub level: Level,
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
 
     /// The timestamp format.
     #[serde(default)]
     pub timestamp: Timestamp,
+
+    /// Whether lines should be prefixed with their `sd-daemon` priority.
+    #[serde(default)]
+    pub journal: Journal,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
 }
 
 impl ::Config for Config {
@@ -70,33 +90,52 @@ impl ::Factory for Factory {
     type Target = (Async, AsyncGuard);
 
     fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
-        build(cfg)
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
     }
 }
 
-fn build(cfg: &Config) -> Result<(Async, AsyncGuard), Error> {
-    match cfg.target {
-        Target::Stdout => build_1(cfg, PlainDecorator::new(std::io::stdout())),
-        Target::Stderr => build_1(cfg, PlainDecorator::new(std::io::stderr())),
-        Target::File { ref path, mode } => {
-            let mut opt = std::fs::OpenOptions::new();
-
-            match mode {
-                OpenMode::Append => opt.create(true).write(true).append(true),
-                OpenMode::Truncate => opt.create(true).write(true).truncate(true),
-                OpenMode::New => opt.create_new(true).write(true),
-            };
 
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+/// Factory for a synchronous drain of type `plain`.
+pub struct DrainFactory;
 
-            build_1(cfg, PlainDecorator::new(opt.open(path)?))
-        },
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
     }
 }
 
-fn build_1<D>(cfg: &Config, decorator: D) -> Result<(Async, AsyncGuard), Error>
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let stream = match cfg.target {
+        Target::Stdout => Some(Stream::Stdout),
+        Target::Stderr => Some(Stream::Stderr),
+        Target::File { .. } | Target::Network { .. } | Target::Ring { .. } => None,
+    };
+
+    let output = ctx.open(&cfg.target)?;
+    let dropped = output.dropped();
+
+    if journal::is_enabled(cfg.journal, stream) {
+        let priority = Priority::new();
+        let output = PriorityPrefix::new(output, priority.clone());
+
+        build_1(cfg, ctx, PlainDecorator::new(output), Some(priority), dropped)
+    } else {
+        build_1(cfg, ctx, PlainDecorator::new(output), None, dropped)
+    }
+}
+
+fn build_1<D>(cfg: &Config, ctx: &Context, decorator: D, priority: Option<Priority>, dropped: Dropped) -> Result<BoxDrain, Error>
 where
     D: Decorator + Send + 'static,
 {
@@ -109,13 +148,9 @@ where
                 Timestamp::Rfc3339Local => format.use_custom_timestamp(timestamp_iso8601_local),
             };
 
-            let format = format
-                .use_original_order()
-                .build()
-                .filter_level(cfg.level.into())
-                .fuse();
+            let format = format.use_original_order().build();
 
-            build_2(cfg, format)
+            build_2(cfg, ctx, format, priority, dropped)
         },
         Format::Compact => {
             let format = CompactFormat::new(decorator);
@@ -125,18 +160,28 @@ where
                 Timestamp::Rfc3339Local => format.use_custom_timestamp(timestamp_iso8601_local),
             };
 
-            let format = format.build().filter_level(cfg.level.into()).fuse();
+            let format = format.build();
 
-            build_2(cfg, format)
+            build_2(cfg, ctx, format, priority, dropped)
         },
     }
 }
 
-fn build_2<D>(_cfg: &Config, drain: D) -> Result<(Async, AsyncGuard), Error>
+fn build_2<D>(cfg: &Config, ctx: &Context, drain: D, priority: Option<Priority>, dropped: Dropped) -> Result<BoxDrain, Error>
 where
-    D: Drain<Err = Never, Ok = ()> + Send + 'static,
+    D: Drain<Err = std::io::Error, Ok = ()> + Send + 'static,
 {
-    Ok(Async::new(drain).build_with_guard())
+    let drain: BoxDrain = match priority {
+        Some(priority) => Box::new(Prioritize::new(drain, priority)),
+        None => Box::new(drain),
+    };
+
+    let filter = LevelFilter::new(Reporting::new(drain, dropped), cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
 }
 
 fn timestamp_iso8601_utc(w: &mut std::io::Write) -> std::io::Result<()> {
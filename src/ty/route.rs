@@ -0,0 +1,202 @@
+//! Configuration for a `route`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type dispatches each record to exactly one of its nested
+//! loggers. The nested logger is selected by an ordered list of routes, each
+//! matching records by their level, tag, and/or module. The first matching
+//! route is taken. Records not matching any route are passed to the default
+//! logger, or discarded if no default is specified.
+//!
+//! Errors of a nested logger are not passed on. Records a nested logger fails
+//! to log are discarded and reported to it once it succeeds again (see
+//! [`Discarding`](::drain::Discarding)).
+//!
+//! The default factories build the drains of the nested configurations within
+//! the context they are invoked in, by default via the default drain factories
+//! (see [`Context`](::Context)). The `Async` factory wraps the combined drain
+//! in a single `Async` drain, thus only one `AsyncGuard` has to be kept alive.
+
+use {BoxDrain, Context, Error};
+use drain::Discarding;
+pub use common::{AsyncWorker, Level};
+use common::is_submodule;
+
+use std;
+
+use slog::{self, Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+
+/// Configuration for a logger of type `route`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// The routes to match records against, in order.
+    #[serde(default)]
+    pub routes: Vec<Route>,
+
+    /// The configuration of the logger receiving all records not matching
+    /// any route.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Box<::Config>>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "route"
+    }
+}
+
+
+/// A route matching records and the nested logger they are dispatched to.
+///
+/// A record matches a route if it matches all of its specified conditions. A
+/// route without any conditions matches all records.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Route {
+    /// Matches records at least as important as the specified level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+
+    /// Matches records with exactly the specified tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    /// Matches records emitted from the specified module or any of its
+    /// submodules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    /// The configuration of the logger receiving all records matching this
+    /// route.
+    pub drain: Box<::Config>,
+}
+
+
+/// Factory for an `Async` drain of type `route`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `route`.
+///
+/// Nested drains are built via the registry of the build context.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let routes = cfg.routes
+        .iter()
+        .map(|route| {
+            Ok(Branch {
+                level: route.level.map(Into::into),
+                tag: route.tag.clone(),
+                module: route.module.clone(),
+                drain: Discarding::new(ctx.build(route.drain.as_ref())?),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let default = match cfg.default {
+        Some(ref cfg) => Some(Discarding::new(ctx.build(cfg.as_ref())?)),
+        None => None,
+    };
+
+    Ok(Box::new(Router { routes, default }))
+}
+
+
+/// A `Drain` passing each record to the drain of the first matching branch.
+///
+/// Errors are handled per branch, thus this drain never fails.
+struct Router {
+    routes:  Vec<Branch>,
+    default: Option<Discarding<BoxDrain>>,
+}
+
+struct Branch {
+    level:  Option<slog::Level>,
+    tag:    Option<String>,
+    module: Option<String>,
+    drain:  Discarding<BoxDrain>,
+}
+
+impl Branch {
+    fn matches(&self, record: &Record) -> bool {
+        if let Some(level) = self.level {
+            if !record.level().is_at_least(level) {
+                return false;
+            }
+        }
+
+        if let Some(ref tag) = self.tag {
+            if record.tag() != tag {
+                return false;
+            }
+        }
+
+        if let Some(ref module) = self.module {
+            if !is_submodule(record.module(), module) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Drain for Router {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let drain = self.routes
+            .iter()
+            .find(|branch| branch.matches(record))
+            .map(|branch| &branch.drain)
+            .or_else(|| self.default.as_ref());
+
+        if let Some(drain) = drain {
+            let _ = drain.log(record, values);
+        }
+
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: slog::Level) -> bool {
+        self.routes.iter().any(|branch| branch.drain.is_enabled(level))
+            || self.default.as_ref().map_or(false, |drain| drain.is_enabled(level))
+    }
+}
+
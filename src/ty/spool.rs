@@ -0,0 +1,831 @@
+//! Configuration for a `spool`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type passes all records to its nested logger. If the
+//! nested logger fails with an I/O error or takes longer than the configured
+//! `slow` threshold, subsequent records are appended to an on-disk spool
+//! instead. A background thread periodically retries the nested logger and
+//! replays the spooled records in order once it has recovered. While records
+//! are spooled, new records are spooled as well, so that records always reach
+//! the nested logger in the order in which they have been logged.
+//!
+//! The spool is a directory containing numbered segment files (e.g.
+//! `00000000000000000001.spool`) and a `head` file recording how far the
+//! oldest segment has already been replayed and how many records have been
+//! dropped. Segments left over from a previous run are replayed at startup.
+//! Records are delivered at least once, i.e. records replayed shortly before a
+//! crash may be replayed again.
+//!
+//! Spooled records are stored with their level, tag, location, message, and
+//! the key-value pairs of the record and logger formatted as strings. Replayed
+//! records carry an additional `spooled_at` pair containing the time at which
+//! the record was originally logged. If the spool exceeds its maximum size,
+//! records are dropped according to the [`Full`](Full) policy. Records which
+//! cannot be appended to the spool (e.g. because the disk is full) are dropped
+//! as well, as are records exceeding the segment size and records found to be
+//! truncated when replaying them. Once the spool has been replayed, a warning
+//! containing the number of dropped records is emitted to the nested logger.
+//! Errors while replaying records are reported to the nested logger as
+//! warning, too. Thus, the drain never fails.
+//!
+//! The default factories build the drain of the nested configuration within
+//! the context they are invoked in, by default via the default drain factories
+//! (see [`Context`](::Context)). The `Async` factory wraps the combined drain
+//! in a single `Async` drain, thus only one `AsyncGuard` has to be kept alive.
+
+use {BoxDrain, Context, Error};
+use drain::report_dropped;
+use kv;
+use msgpack::{self, Value};
+pub use common::{AsyncWorker, Duration, Size};
+
+use std;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Instant;
+
+use slog::{self, BorrowedKV, Drain, Level, OwnedKVList, Record, RecordLocation, RecordStatic, KV};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::Utc;
+
+
+/// The number of records replayed before the lock on the spool is released.
+const REPLAY_BATCH: usize = 100;
+
+
+/// Configuration for a logger of type `spool`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The configuration of the logger records are passed to.
+    pub drain: Box<::Config>,
+
+    /// The directory containing the spool.
+    pub path: PathBuf,
+
+    /// The maximum total size of all spooled records.
+    #[serde(default = "default::max_size", rename = "max-size")]
+    pub max_size: Size,
+
+    /// The maximum size of a single segment file.
+    #[serde(default = "default::segment_size", rename = "segment-size")]
+    pub segment_size: Size,
+
+    /// Which records to drop if the spool is full.
+    #[serde(default)]
+    pub full: Full,
+
+    /// The interval after which a failed nested logger should be retried.
+    #[serde(default = "default::retry")]
+    pub retry: Duration,
+
+    /// The time after which a nested logger is considered slow.
+    ///
+    /// A slow nested logger is treated like a failed one, i.e. records are
+    /// spooled until it is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow: Option<Duration>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "spool"
+    }
+}
+
+
+/// Policy determining which records to drop if the spool is full.
+///
+/// Defaults to [`DropOldest`](Full::DropOldest).
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Full {
+    /// Drop the oldest segment to make room for new records.
+    DropOldest,
+
+    /// Drop new records until there is room in the spool.
+    DropNewest,
+}
+
+impl Default for Full {
+    fn default() -> Self {
+        Full::DropOldest
+    }
+}
+
+
+/// Factory for an `Async` drain of type `spool`.
+///
+/// The nested drain is built via the registry of the build context.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `spool`.
+///
+/// The nested drain is built via the registry of the build context.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.segment_size.bytes() > cfg.max_size.bytes() {
+        return Err(Error::msg("spool segment size must not exceed the maximum spool size"));
+    }
+
+    let drain = ctx.build(cfg.drain.as_ref())?;
+    let spool = Spool::open(&cfg.path, cfg.segment_size.bytes(), cfg.max_size.bytes(), cfg.full)?;
+
+    let retry: std::time::Duration = cfg.retry.into();
+
+    let state = Arc::new(Mutex::new(State {
+        drain,
+        spool,
+        retry,
+        slow: cfg.slow.map(Into::into),
+        retry_at: None,
+        failing: false,
+    }));
+
+    spawn_replayer(Arc::downgrade(&state), std::cmp::min(retry, std::time::Duration::from_secs(1)));
+
+    Ok(Box::new(Spooling(state)))
+}
+
+/// Periodically replays spooled records until the drain has been dropped.
+fn spawn_replayer(state: Weak<Mutex<State>>, interval: std::time::Duration) {
+    thread::spawn(move || loop {
+        match state.upgrade() {
+            Some(state) => loop {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+
+                match state.replay() {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        state.warn(&e);
+                        break;
+                    },
+                }
+            },
+            None => break,
+        }
+
+        thread::sleep(interval);
+    });
+}
+
+
+mod default {
+    use common::{Duration, Size};
+
+    pub fn max_size() -> Size { Size(64 * 1024 * 1024) }
+    pub fn segment_size() -> Size { Size(1024 * 1024) }
+    pub fn retry() -> Duration { Duration::from_secs(10) }
+}
+
+
+/// A `Drain` spooling records to disk while its nested drain fails.
+struct Spooling(Arc<Mutex<State>>);
+
+impl Drain for Spooling {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        state.log(record, values);
+
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        let state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        state.drain.is_enabled(level)
+    }
+}
+
+
+struct State {
+    drain:    BoxDrain,
+    spool:    Spool,
+    retry:    std::time::Duration,
+    slow:     Option<std::time::Duration>,
+
+    /// The time at which the nested drain should be retried, `None` if it is
+    /// in use.
+    retry_at: Option<Instant>,
+
+    /// Whether replaying has failed and this has already been reported.
+    failing:  bool,
+}
+
+impl State {
+    fn is_due(&self) -> bool {
+        self.retry_at.map_or(true, |time| Instant::now() >= time)
+    }
+
+    fn log(&mut self, record: &Record, values: &OwnedKVList) {
+        if self.spool.is_empty() && self.is_due() {
+            let start = Instant::now();
+
+            match self.drain.log(record, values) {
+                Ok(()) => {
+                    self.retry_at = match self.slow {
+                        Some(slow) if start.elapsed() >= slow => Some(Instant::now() + self.retry),
+                        _ => None,
+                    };
+
+                    return;
+                },
+                Err(_) => self.retry_at = Some(Instant::now() + self.retry),
+            }
+        }
+
+        self.spool.push(&encode(record, values));
+    }
+
+    /// Reports a failure to replay spooled records to the nested drain, once
+    /// until replaying succeeds again.
+    fn warn(&mut self, error: &io::Error) {
+        if self.failing {
+            return;
+        }
+
+        let values = OwnedKVList::from(o!());
+        let error = error.to_string();
+
+        self.failing = self
+            .drain
+            .log(
+                &record!(
+                    Level::Warning,
+                    "",
+                    &format_args!("failed to replay spooled records"),
+                    b!("error" => error.as_str())
+                ),
+                &values,
+            )
+            .is_ok();
+    }
+
+    /// Replays a batch of spooled records, returning `true` if there are more
+    /// records to replay.
+    fn replay(&mut self) -> io::Result<bool> {
+        if self.spool.is_empty() || !self.is_due() {
+            return Ok(false);
+        }
+
+        for _ in 0..REPLAY_BATCH {
+            let entry = match self.spool.front()? {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            match Entry::decode(&entry) {
+                Some(record) => {
+                    if record.log(&self.drain).is_err() {
+                        self.retry_at = Some(Instant::now() + self.retry);
+                        self.spool.commit()?;
+                        return Ok(false);
+                    }
+                },
+                None => {
+                    self.spool.dropped += 1;
+                    self.spool.reason = "corrupt spooled record".into();
+                },
+            }
+
+            self.spool.advance(entry.len())?;
+        }
+
+        self.spool.commit()?;
+        self.failing = false;
+
+        if !self.spool.is_empty() {
+            return Ok(true);
+        }
+
+        self.retry_at = None;
+
+        if self.spool.dropped > 0 {
+            let dropped = self.spool.dropped;
+            let reported = report_dropped(dropped, &self.spool.reason, |record, values| {
+                self.drain.log(record, values)
+            });
+
+            if reported.is_ok() {
+                self.spool.dropped = 0;
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+
+/// A directory of segment files forming a queue of records.
+struct Spool {
+    dir:          PathBuf,
+    segments:     VecDeque<Segment>,
+    offset:       u64,
+    writer:       Option<File>,
+    segment_size: u64,
+    max_size:     u64,
+    full:         Full,
+    next_seq:     u64,
+    dropped:      u64,
+    reason:       String,
+}
+
+struct Segment {
+    seq:  u64,
+    size: u64,
+}
+
+impl Spool {
+    /// Opens the spool in the specified directory, creating it if necessary.
+    ///
+    /// New records are never appended to segments left over from a previous
+    /// run.
+    fn open(dir: &Path, segment_size: u64, max_size: u64, full: Full) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+
+            if let Some(seq) = name.to_str().and_then(parse_segment_name) {
+                found.push(Segment {
+                    seq,
+                    size: entry.metadata()?.len(),
+                });
+            }
+        }
+
+        found.sort_by_key(|segment| segment.seq);
+
+        let (head, mut offset, dropped) = read_head(&dir.join("head"));
+
+        let mut segments = VecDeque::new();
+        for segment in found {
+            if segment.seq < head {
+                fs::remove_file(dir.join(segment_name(segment.seq)))?;
+            } else {
+                segments.push_back(segment);
+            }
+        }
+
+        if segments.front().map(|segment| segment.seq) != Some(head) {
+            offset = 0;
+        }
+
+        let next_seq = segments.back().map_or(head, |segment| segment.seq) + 1;
+
+        let mut spool = Spool {
+            dir: dir.to_owned(),
+            segments,
+            offset,
+            writer: None,
+            segment_size,
+            max_size,
+            full,
+            next_seq,
+            dropped,
+            reason: "dropped in a previous run".into(),
+        };
+
+        spool.trim()?;
+        Ok(spool)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the size of all records that have not been replayed yet.
+    fn size(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size).sum::<u64>() - self.offset
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(segment_name(seq))
+    }
+
+    /// Appends a record, dropping records if the spool is full. The record is
+    /// dropped as well if it cannot be appended.
+    fn push(&mut self, entry: &[u8]) {
+        if let Err(e) = self.push_1(entry) {
+            self.dropped += 1;
+            self.reason = e.to_string();
+        }
+    }
+
+    fn push_1(&mut self, entry: &[u8]) -> io::Result<()> {
+        let len = entry.len() as u64 + 4;
+
+        // such a record could not be replayed
+        if len > self.segment_size {
+            self.dropped += 1;
+            self.reason = "record exceeds the spool segment size".into();
+            return Ok(());
+        }
+
+        while self.size() + len > self.max_size {
+            self.reason = "spool exceeded its limits".into();
+
+            if self.full == Full::DropNewest || self.is_empty() {
+                self.dropped += 1;
+                return Ok(());
+            }
+
+            self.dropped += self.drop_front()?;
+        }
+
+        let rotate = match (self.segments.back(), self.writer.is_some()) {
+            (Some(back), true) => back.size > 0 && back.size + len > self.segment_size,
+            _ => true,
+        };
+
+        if rotate {
+            let seq = self.next_seq;
+            let file = OpenOptions::new().create_new(true).append(true).open(self.path(seq))?;
+
+            self.next_seq += 1;
+            self.writer = Some(file);
+            self.segments.push_back(Segment { seq, size: 0 });
+        }
+
+        let mut buf = Vec::with_capacity(len as usize);
+        buf.extend_from_slice(&(entry.len() as u32).to_be_bytes());
+        buf.extend_from_slice(entry);
+
+        let result = match self.writer {
+            Some(ref mut writer) => writer.write_all(&buf),
+            None => unreachable!(),
+        };
+
+        match result {
+            Ok(()) => {
+                if let Some(back) = self.segments.back_mut() {
+                    back.size += len;
+                }
+                Ok(())
+            },
+            Err(e) => {
+                // a partially written record ends the segment
+                self.writer = None;
+                Err(e)
+            },
+        }
+    }
+
+    /// Returns the oldest record without removing it.
+    fn front(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let seq = match self.segments.front() {
+                Some(segment) => segment.seq,
+                None => return Ok(None),
+            };
+
+            let mut file = File::open(self.path(seq))?;
+            file.seek(SeekFrom::Start(self.offset))?;
+
+            match read_entry(&mut file, self.segment_size) {
+                Ok(entry) => return Ok(Some(entry)),
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    // truncated by a crash or a failed write, which ends the
+                    // segment, thus only the last record is lost
+                    self.dropped += 1;
+                    self.reason = "corrupt spooled record".into();
+                    self.offset = self.segments.front().map_or(0, |segment| segment.size);
+                    self.trim()?;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Removes the oldest record, given the length returned by `front()`.
+    fn advance(&mut self, len: usize) -> io::Result<()> {
+        self.offset += len as u64 + 4;
+        self.trim()
+    }
+
+    /// Removes the oldest segment, returning the number of records dropped.
+    fn drop_front(&mut self) -> io::Result<u64> {
+        let seq = match self.segments.front() {
+            Some(segment) => segment.seq,
+            None => return Ok(0),
+        };
+
+        let mut file = File::open(self.path(seq))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+
+        let mut count = 0;
+        let mut len = [0; 4];
+        while file.read_exact(&mut len).is_ok() {
+            file.seek(SeekFrom::Current(i64::from(u32::from_be_bytes(len))))?;
+            count += 1;
+        }
+
+        self.offset = self.segments.front().map_or(0, |segment| segment.size);
+        self.trim()?;
+
+        Ok(count)
+    }
+
+    /// Removes all fully replayed segments.
+    fn trim(&mut self) -> io::Result<()> {
+        while let Some((seq, size)) = self.segments.front().map(|s| (s.seq, s.size)) {
+            if self.offset < size {
+                break;
+            }
+
+            if self.segments.len() == 1 {
+                self.writer = None;
+            }
+
+            self.segments.pop_front();
+            self.offset = 0;
+
+            fs::remove_file(self.path(seq))?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the replay position and the number of dropped records, so that
+    /// they survive restarts.
+    fn commit(&self) -> io::Result<()> {
+        let head = self.dir.join("head");
+
+        let seq = match self.segments.front() {
+            Some(segment) => segment.seq,
+            None => {
+                return match fs::remove_file(&head) {
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                    result => result,
+                };
+            },
+        };
+
+        let tmp = self.dir.join("head.tmp");
+        fs::write(&tmp, format!("{} {} {}\n", seq, self.offset, self.dropped))?;
+        fs::rename(tmp, head)
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
+
+fn segment_name(seq: u64) -> String {
+    format!("{:020}.spool", seq)
+}
+
+fn parse_segment_name(name: &str) -> Option<u64> {
+    name.strip_suffix(".spool").and_then(|seq| seq.parse().ok())
+}
+
+/// Reads the sequence number of the oldest segment, the replay position within
+/// it, and the number of dropped records.
+fn read_head(path: &Path) -> (u64, u64, u64) {
+    let head = fs::read_to_string(path).unwrap_or_default();
+    let mut parts = head.split_whitespace().map(|part| part.parse().ok());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(seq)), Some(Some(offset)), Some(Some(dropped))) => (seq, offset, dropped),
+        _ => (0, 0, 0),
+    }
+}
+
+fn read_entry<R: Read>(r: &mut R, max: u64) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+
+    // a length exceeding the segment size can only stem from a torn write
+    let len = u64::from(u32::from_be_bytes(len));
+    if len > max {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "corrupt spool segment"));
+    }
+
+    let mut entry = vec![0; len as usize];
+    r.read_exact(&mut entry)?;
+    Ok(entry)
+}
+
+
+/// Encodes a record as MessagePack array.
+fn encode(record: &Record, values: &OwnedKVList) -> Vec<u8> {
+    let pairs = kv::collect(record, values);
+    let mut buf = Vec::new();
+
+    msgpack::write_array_len(&mut buf, 10);
+    msgpack::write_uint(&mut buf, record.level().as_usize() as u64);
+    msgpack::write_str(&mut buf, &Utc::now().to_rfc3339());
+    msgpack::write_str(&mut buf, record.tag());
+    msgpack::write_str(&mut buf, record.module());
+    msgpack::write_str(&mut buf, record.file());
+    msgpack::write_uint(&mut buf, u64::from(record.line()));
+    msgpack::write_uint(&mut buf, u64::from(record.column()));
+    msgpack::write_str(&mut buf, record.function());
+    msgpack::write_str(&mut buf, &record.msg().to_string());
+
+    msgpack::write_map_len(&mut buf, pairs.len());
+    for (key, value) in &pairs {
+        msgpack::write_str(&mut buf, key);
+        msgpack::write_str(&mut buf, value);
+    }
+
+    buf
+}
+
+/// A decoded spooled record.
+struct Entry {
+    level:    Level,
+    time:     String,
+    tag:      String,
+    module:   String,
+    file:     String,
+    line:     u32,
+    column:   u32,
+    function: String,
+    msg:      String,
+    pairs:    Vec<(String, String)>,
+}
+
+impl Entry {
+    fn decode(mut buf: &[u8]) -> Option<Self> {
+        let mut fields = match msgpack::read(&mut buf).ok()? {
+            Value::Array(fields) if fields.len() == 10 => fields.into_iter(),
+            _ => return None,
+        };
+
+        let mut next = || fields.next().unwrap_or(Value::Nil);
+
+        let uint = |value: Value| match value {
+            Value::Uint(n) => Some(n),
+            _ => None,
+        };
+
+        let string = |value: Value| match value {
+            Value::Str(s) => Some(s),
+            _ => None,
+        };
+
+        let level = Level::from_usize(uint(next())? as usize)?;
+        let time = string(next())?;
+        let tag = string(next())?;
+        let module = string(next())?;
+        let file = string(next())?;
+        let line = uint(next())? as u32;
+        let column = uint(next())? as u32;
+        let function = string(next())?;
+        let msg = string(next())?;
+
+        let pairs = match next() {
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(key, value)| Some((string(key)?, string(value)?)))
+                .collect::<Option<Vec<_>>>()?,
+            _ => return None,
+        };
+
+        Some(Entry {
+            level,
+            time,
+            tag,
+            module,
+            file,
+            line,
+            column,
+            function,
+            msg,
+            pairs,
+        })
+    }
+
+    fn log(&self, drain: &BoxDrain) -> io::Result<()> {
+        let rstatic = RecordStatic {
+            location: location(&self.file, self.line, self.column, &self.function, &self.module),
+            tag:      &self.tag,
+            level:    self.level,
+        };
+
+        let pairs = Pairs {
+            pairs:      &self.pairs,
+            spooled_at: &self.time,
+        };
+
+        let values = OwnedKVList::from(o!());
+
+        drain.log(
+            &Record::new(&rstatic, &format_args!("{}", self.msg), BorrowedKV(&pairs)),
+            &values,
+        )
+    }
+}
+
+/// The key-value pairs of a replayed record.
+struct Pairs<'a> {
+    pairs:      &'a [(String, String)],
+    spooled_at: &'a str,
+}
+
+impl<'a> KV for Pairs<'a> {
+    fn serialize(&self, _record: &Record, serializer: &mut slog::Serializer) -> slog::Result {
+        for (key, value) in self.pairs {
+            serializer.emit_str(intern(key), value)?;
+        }
+
+        serializer.emit_str("spooled_at", self.spooled_at)
+    }
+}
+
+
+/// Returns a static copy of the specified string.
+///
+/// Records require static keys and locations. Spooled strings are therefore
+/// leaked once and reused afterwards, which is bounded by the number of
+/// distinct keys and call sites.
+fn intern(s: &str) -> &'static str {
+    lazy_static! {
+        static ref STRINGS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    }
+
+    let mut strings = STRINGS.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(s) = strings.get(s) {
+        return s;
+    }
+
+    let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+    strings.insert(s);
+    s
+}
+
+/// Returns a static record location, see [`intern`](intern).
+fn location(
+    file: &str,
+    line: u32,
+    column: u32,
+    function: &str,
+    module: &str,
+) -> &'static RecordLocation {
+    type Key = (&'static str, u32, u32, &'static str, &'static str);
+
+    lazy_static! {
+        static ref LOCATIONS: Mutex<BTreeMap<Key, &'static RecordLocation>> =
+            Mutex::new(BTreeMap::new());
+    }
+
+    let key = (intern(file), line, column, intern(function), intern(module));
+    let mut locations = LOCATIONS.lock().unwrap_or_else(|e| e.into_inner());
+
+    locations.entry(key).or_insert_with(|| {
+        Box::leak(Box::new(RecordLocation {
+            file:     key.0,
+            line:     key.1,
+            column:   key.2,
+            function: key.3,
+            module:   key.4,
+        }))
+    })
+}
@@ -0,0 +1,328 @@
+//! Configuration for a `sqlite`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type inserts records into a local SQLite database, see the
+//! [`sqlite`](::sqlite) module for the schema and for a helper to query
+//! records. Records are inserted in batches, each within a single
+//! transaction. A batch is committed once it contains `batch` records, or
+//! after `interval` has passed, whichever comes first. When building an
+//! `Async` drain, records are thus batched and inserted from the thread of the
+//! `Async` drain, apart from batches committed after `interval`, which are
+//! committed from a background thread.
+//!
+//! Old records are deleted whenever a batch is committed, if the table exceeds
+//! `max-rows` records or if records are older than `max-age`.
+//!
+//! Records of a batch which could not be committed are kept and committed with
+//! the next batch. At most `queue` records are kept, older records are dropped
+//! while the queue is full. Dropped records are reported in a warning record
+//! inserted with the next batch committed successfully. Records which cannot
+//! be committed when the drain is dropped are lost.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use kv;
+use sqlite;
+pub use common::{AsyncWorker, Directives, Duration, Level};
+
+use std;
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::Utc;
+
+
+/// Configuration for a logger of type `sqlite`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The database file, created if it does not exist.
+    pub path: PathBuf,
+
+    /// The maximum number of records inserted in a single transaction.
+    #[serde(default = "default::batch")]
+    pub batch: usize,
+
+    /// The maximum time after which pending records are inserted.
+    #[serde(default = "default::interval")]
+    pub interval: Duration,
+
+    /// The maximum number of records waiting to be inserted.
+    #[serde(default = "default::queue")]
+    pub queue: usize,
+
+    /// The maximum number of records to keep.
+    #[serde(default, rename = "max-rows", skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<u64>,
+
+    /// The maximum age of records to keep.
+    #[serde(default, rename = "max-age", skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<Duration>,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "sqlite"
+    }
+}
+
+
+/// Factory for an `Async` drain of type `sqlite`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `sqlite`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    if cfg.batch == 0 {
+        return Err(Error::msg("sqlite batch size must be at least 1"));
+    }
+
+    if cfg.queue < cfg.batch {
+        return Err(Error::msg("sqlite queue must be at least the batch size"));
+    }
+
+    let conn = open(&cfg.path).map_err(|e| {
+        Error::msg(&format!("failed to open database '{}': {}", cfg.path.display(), e))
+    })?;
+
+    let state = Arc::new(Mutex::new(State {
+        conn,
+        pending: VecDeque::with_capacity(cfg.batch),
+        batch: cfg.batch,
+        queue: cfg.queue,
+        max_rows: cfg.max_rows,
+        max_age: cfg.max_age.map(Into::into),
+        dropped: 0,
+        reason: String::new(),
+    }));
+
+    spawn_committer(Arc::downgrade(&state), cfg.interval.into());
+
+    let filter = LevelFilter::new(Sqlite(state), cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+/// Opens the database and creates the schema, if necessary.
+fn open(path: &PathBuf) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(sqlite::BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.execute_batch(sqlite::SCHEMA)?;
+    Ok(conn)
+}
+
+/// Periodically commits pending records until the drain has been dropped.
+fn spawn_committer(state: Weak<Mutex<State>>, interval: std::time::Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        match state.upgrade() {
+            Some(state) => state.lock().unwrap_or_else(|e| e.into_inner()).flush(),
+            None => break,
+        }
+    });
+}
+
+
+mod default {
+    use common::Duration;
+
+    pub fn batch() -> usize { 100 }
+    pub fn interval() -> Duration { Duration::from_secs(1) }
+    pub fn queue() -> usize { 10_000 }
+}
+
+
+/// A record waiting to be inserted.
+struct Row {
+    ts:     i64,
+    level:  &'static str,
+    module: &'static str,
+    tag:    String,
+    msg:    String,
+    kv:     String,
+}
+
+impl Row {
+    fn new(record: &Record, values: &OwnedKVList) -> Self {
+        let mut obj = Map::new();
+
+        for (key, value) in kv::collect(record, values) {
+            obj.entry(key).or_insert(Value::String(value));
+        }
+
+        Row {
+            ts: sqlite::nanos(Utc::now()),
+            level: Level::from(record.level()).to_str_lowercase(),
+            module: record.module(),
+            tag: record.tag().into(),
+            msg: record.msg().to_string(),
+            // serializing a map of json values never fails
+            kv: serde_json::to_string(&Value::Object(obj)).unwrap_or_default(),
+        }
+    }
+}
+
+
+/// The shared state of the drain and its committer thread.
+struct State {
+    conn:     Connection,
+    pending:  VecDeque<Row>,
+    batch:    usize,
+    queue:    usize,
+    max_rows: Option<u64>,
+    max_age:  Option<std::time::Duration>,
+    dropped:  u64,
+    reason:   String,
+}
+
+impl State {
+    /// Queues a record, dropping the oldest pending record if the queue is
+    /// full, and commits the pending records once they form a full batch.
+    fn push(&mut self, row: Row) {
+        if self.pending.len() >= self.queue {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+
+        self.pending.push_back(row);
+
+        if self.pending.len() >= self.batch {
+            self.flush();
+        }
+    }
+
+    /// Commits all pending records, remembering the error as reason for
+    /// records dropped later on.
+    fn flush(&mut self) {
+        if let Err(e) = self.commit() {
+            self.reason = format!("failed to insert records into database: {}", e);
+        }
+    }
+
+    /// Inserts all pending records in a single transaction, preceded by a
+    /// report of the records dropped since the last commit, and applies the
+    /// retention limits.
+    ///
+    /// Pending records are kept if the transaction fails.
+    fn commit(&mut self) -> rusqlite::Result<()> {
+        if self.pending.is_empty() && self.dropped == 0 {
+            return Ok(());
+        }
+
+        let report = match self.dropped {
+            0 => None,
+            n => Some(report_dropped(n, &self.reason, Row::new)),
+        };
+
+        let tx = self.conn.transaction()?;
+
+        {
+            let mut insert = tx.prepare_cached(sqlite::INSERT)?;
+
+            for row in report.iter().chain(&self.pending) {
+                insert.execute((row.ts, row.level, row.module, &row.tag, &row.msg, &row.kv))?;
+            }
+        }
+
+        if let Some(max_rows) = self.max_rows {
+            tx.execute(
+                "DELETE FROM records WHERE id <= \
+                 (SELECT id FROM records ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+                [max_rows as i64],
+            )?;
+        }
+
+        if let Some(max_age) = self.max_age {
+            let max_age = std::cmp::min(max_age.as_nanos(), i64::MAX as u128) as i64;
+            let cutoff = sqlite::nanos(Utc::now()).saturating_sub(max_age);
+
+            tx.execute("DELETE FROM records WHERE ts < ?1", [cutoff])?;
+        }
+
+        tx.commit()?;
+        self.pending.clear();
+        self.dropped = 0;
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // there is no output left to report a failure to
+        let _ = self.commit();
+    }
+}
+
+
+/// A `Drain` inserting records into a SQLite database.
+struct Sqlite(Arc<Mutex<State>>);
+
+impl Drain for Sqlite {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let row = Row::new(record, values);
+
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(row);
+        Ok(())
+    }
+}
@@ -0,0 +1,567 @@
+//! Configuration for a `syslog`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
+//!
+//! A logger of this type sends each record as syslog message, either to the
+//! local syslog daemon or to a remote endpoint via UDP or TCP. Messages are
+//! formatted according to RFC 5424 (with the key-value pairs of the record as
+//! structured data) or the legacy BSD format described in RFC 3164 (with the
+//! key-value pairs appended to the message).
+//!
+//! Levels are mapped to syslog severities via
+//! [`Level::severity`](::common::Level::severity). Messages sent via TCP are
+//! framed by octet-counting (RFC 5424) or terminated by a newline (RFC 3164),
+//! as described in RFC 6587.
+//!
+//! Messages are sent synchronously and are not buffered. If a message cannot
+//! be sent, a new connection is attempted with an increasing delay (up to the
+//! configured backoff) and messages are dropped in the meantime. Once sending
+//! succeeds again, the number of dropped messages is reported in a warning
+//! message. Thus, the drain never fails after it has been built.
+
+use {BoxDrain, Context, Error};
+use drain::{report_dropped, LevelFilter};
+use host;
+use kv;
+use net::{connect_tcp, Connection, Reconnect, CONNECT_TIMEOUT};
+pub use common::{AsyncWorker, Directives, Duration, Level};
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use serde::{Serialize, Serializer};
+use slog::{Drain, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+
+use chrono::{Local, Utc};
+
+
+/// Configuration for a logger of type `syslog`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// The syslog daemon or endpoint to which messages should be sent.
+    #[serde(default)]
+    pub target: Target,
+
+    /// The format of the messages.
+    #[serde(default)]
+    pub format: Format,
+
+    /// The facility of the messages.
+    #[serde(default)]
+    pub facility: Facility,
+
+    /// The application name included in the messages.
+    ///
+    /// Defaults to the file name of the current executable.
+    #[serde(default, rename = "app-name", skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+
+    /// The hostname included in the messages.
+    ///
+    /// Defaults to the name of the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// The maximum delay between two connection attempts.
+    #[serde(default = "default::backoff")]
+    pub backoff: Duration,
+
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
+    #[serde(default)]
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            target: Target::default(),
+            format: Format::default(),
+            facility: Facility::default(),
+            app_name: None,
+            hostname: None,
+            backoff: default::backoff(),
+            level: Directives::default(),
+            handle: None,
+            worker: AsyncWorker::default(),
+        }
+    }
+}
+
+impl ::Config for Config {
+    fn ty(&self) -> &'static str {
+        "syslog"
+    }
+}
+
+
+/// The syslog daemon or endpoint to which messages should be sent.
+///
+/// Defaults to [`Local`](Target::Local).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// The local syslog daemon, via the first existing socket of `/dev/log`,
+    /// `/var/run/syslog`, and `/var/run/log`.
+    Local,
+
+    /// A unix datagram socket at the specified path.
+    Unix(PathBuf),
+
+    /// A UDP endpoint specified as `host:port`.
+    Udp(String),
+
+    /// A TCP endpoint specified as `host:port`.
+    Tcp(String),
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::Local
+    }
+}
+
+impl Serialize for Target {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let (name, value) = match *self {
+            Target::Local => return serializer.serialize_str("local"),
+            Target::Unix(ref path) => ("unix", path.to_string_lossy()),
+            Target::Udp(ref addr) => ("udp", addr.as_str().into()),
+            Target::Tcp(ref addr) => ("tcp", addr.as_str().into()),
+        };
+
+        let mut state = serializer.serialize_struct("Target", 1)?;
+        state.serialize_field(name, &value)?;
+        state.end()
+    }
+}
+
+
+/// The format of syslog messages.
+///
+/// Defaults to [`Rfc5424`](Format::Rfc5424).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The format described in RFC 5424.
+    ///
+    /// The key-value pairs of a record are emitted as structured data with
+    /// the ID `slog@32473`, the tag of a record is emitted as message ID.
+    Rfc5424,
+
+    /// The legacy BSD format described in RFC 3164.
+    ///
+    /// The key-value pairs of a record are appended to its message.
+    Rfc3164,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Rfc5424
+    }
+}
+
+
+/// A syslog facility.
+///
+/// Defaults to [`User`](Facility::User).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+pub enum Facility {
+    Kern,
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    /// Returns the numerical code of this facility.
+    pub fn code(self) -> u8 {
+        match self {
+            Facility::Kern => 0,
+            Facility::User => 1,
+            Facility::Mail => 2,
+            Facility::Daemon => 3,
+            Facility::Auth => 4,
+            Facility::Syslog => 5,
+            Facility::Lpr => 6,
+            Facility::News => 7,
+            Facility::Uucp => 8,
+            Facility::Cron => 9,
+            Facility::Authpriv => 10,
+            Facility::Ftp => 11,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+impl Default for Facility {
+    fn default() -> Self {
+        Facility::User
+    }
+}
+
+
+/// Factory for an `Async` drain of type `syslog`.
+pub struct Factory;
+
+impl ::Factory for Factory {
+    type Config = Config;
+    type Target = (Async, AsyncGuard);
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `syslog`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
+    }
+}
+
+
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let connection = Connection::new(Transport::open(&cfg.target)?, cfg.backoff.into());
+
+    let drain = Syslog {
+        connection: Mutex::new(connection),
+        format: cfg.format,
+        facility: cfg.facility,
+        hostname: cfg.hostname.clone().or_else(host::hostname).unwrap_or_default(),
+        app_name: cfg.app_name.clone().or_else(host::name).unwrap_or_default(),
+        pid: host::pid(),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
+}
+
+
+mod default {
+    use super::Duration;
+
+    pub fn backoff() -> Duration { Duration::from_secs(30) }
+}
+
+
+/// A `Drain` sending records as syslog messages.
+struct Syslog {
+    connection: Mutex<Connection<Transport>>,
+    format:     Format,
+    facility:   Facility,
+    hostname:   String,
+    app_name:   String,
+    pid:        u64,
+}
+
+impl Syslog {
+    fn format(&self, record: &Record, values: &OwnedKVList) -> String {
+        let severity = Level::from(record.level()).severity();
+        let pri = u32::from(self.facility.code()) * 8 + u32::from(severity);
+        let pairs = kv::collect(record, values);
+
+        let mut msg = String::new();
+
+        // writing to a string never fails
+        match self.format {
+            Format::Rfc5424 => {
+                let _ = write!(
+                    msg,
+                    "<{}>1 {} {} {} {} {} ",
+                    pri,
+                    Utc::now().format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+                    header(&self.hostname, 255),
+                    header(&self.app_name, 48),
+                    self.pid,
+                    header(record.tag(), 32),
+                );
+
+                if pairs.is_empty() {
+                    msg.push('-');
+                } else {
+                    msg.push_str("[slog@32473");
+
+                    for (key, value) in pairs {
+                        let _ = write!(msg, " {}=\"{}\"", param_name(&key), param_value(&value));
+                    }
+
+                    msg.push(']');
+                }
+
+                let _ = write!(msg, " {}", record.msg());
+            },
+            Format::Rfc3164 => {
+                let _ = write!(
+                    msg,
+                    "<{}>{} {} {}[{}]: {}",
+                    pri,
+                    Local::now().format("%b %e %H:%M:%S"),
+                    header(&self.hostname, 255),
+                    header(&self.app_name, 32),
+                    self.pid,
+                    record.msg(),
+                );
+
+                for (key, value) in pairs {
+                    let _ = write!(msg, " {}={}", key, value);
+                }
+            },
+        }
+
+        msg
+    }
+}
+
+impl Drain for Syslog {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Self::Err> {
+        let msg = self.format(record, values);
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        connection.report(|transport, count, reason| {
+            let report = report_dropped(count, reason, |record, values| {
+                self.format(record, values)
+            });
+
+            transport.send(report.as_bytes(), self.format)
+        });
+
+        // the daemon may have been restarted, thus the transport is reconnected
+        if let Err(e) = connection.send(|transport| transport.send(msg.as_bytes(), self.format)) {
+            connection.discard(1, &e);
+        }
+
+        Ok(())
+    }
+}
+
+
+/// The transport to a syslog daemon or endpoint.
+enum Transport {
+    #[cfg(unix)]
+    Unix(PathBuf, UnixDatagram),
+    Udp(UdpSocket),
+    Tcp(String, Option<TcpStream>),
+}
+
+impl Transport {
+    fn open(target: &Target) -> Result<Self, Error> {
+        match *target {
+            Target::Local => {
+                let path = ["/dev/log", "/var/run/syslog", "/var/run/log"]
+                    .iter()
+                    .map(PathBuf::from)
+                    .find(|path| path.exists())
+                    .ok_or_else(|| Error::msg("no local syslog socket found"))?;
+
+                Transport::unix(path)
+            },
+            Target::Unix(ref path) => Transport::unix(path.clone()),
+            Target::Udp(ref addr) => {
+                let addr = addr
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| Error::msg("failed to resolve syslog address"))?;
+
+                let socket = if addr.is_ipv4() {
+                    UdpSocket::bind("0.0.0.0:0")?
+                } else {
+                    UdpSocket::bind("[::]:0")?
+                };
+
+                socket.connect(addr)?;
+                Ok(Transport::Udp(socket))
+            },
+            Target::Tcp(ref addr) => {
+                let stream = connect_tcp(addr, CONNECT_TIMEOUT)?;
+                Ok(Transport::Tcp(addr.clone(), Some(stream)))
+            },
+        }
+    }
+
+    #[cfg(unix)]
+    fn unix(path: PathBuf) -> Result<Self, Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&path)?;
+
+        Ok(Transport::Unix(path, socket))
+    }
+
+    #[cfg(not(unix))]
+    fn unix(_path: PathBuf) -> Result<Self, Error> {
+        Err(Error::msg("unix sockets are not supported on this platform"))
+    }
+
+    /// Sends the specified message via the current connection.
+    fn send(&mut self, msg: &[u8], format: Format) -> io::Result<()> {
+        match *self {
+            #[cfg(unix)]
+            Transport::Unix(_, ref socket) => socket.send(msg).map(|_| ()),
+            Transport::Udp(ref socket) => socket.send(msg).map(|_| ()),
+            Transport::Tcp(_, ref mut stream) => {
+                let mut frame = Vec::with_capacity(msg.len() + 8);
+
+                match format {
+                    Format::Rfc5424 => {
+                        write!(frame, "{} ", msg.len())?;
+                        frame.extend_from_slice(msg);
+                    },
+                    Format::Rfc3164 => {
+                        frame.extend_from_slice(msg);
+                        frame.push(b'\n');
+                    },
+                }
+
+                let result = match *stream {
+                    Some(ref mut s) => s.write_all(&frame),
+                    None => Err(io::Error::new(io::ErrorKind::NotConnected, "not connected")),
+                };
+
+                // a partially written frame cannot be continued
+                if result.is_err() {
+                    *stream = None;
+                }
+
+                result
+            },
+        }
+    }
+}
+
+impl Reconnect for Transport {
+    /// Re-establishes the connection. UDP sockets are connectionless and kept.
+    fn reconnect(&mut self) -> io::Result<()> {
+        match *self {
+            #[cfg(unix)]
+            Transport::Unix(ref path, ref mut socket) => {
+                let new = UnixDatagram::unbound()?;
+                new.connect(path)?;
+                *socket = new;
+            },
+            Transport::Udp(_) => {},
+            Transport::Tcp(ref addr, ref mut stream) => {
+                *stream = Some(connect_tcp(addr, CONNECT_TIMEOUT)?);
+            },
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Sanitizes a header field, replacing all non-printable characters and
+/// spaces and limiting its length.
+fn header(value: &str, max: usize) -> String {
+    if value.is_empty() {
+        return "-".into();
+    }
+
+    value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max)
+        .collect()
+}
+
+/// Sanitizes the name of a structured-data parameter.
+fn param_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '=' | ']' | '"' => '_',
+            c if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .take(32)
+        .collect();
+
+    if name.is_empty() {
+        "_".into()
+    } else {
+        name
+    }
+}
+
+/// Escapes the value of a structured-data parameter.
+fn param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if c == '"' || c == '\\' || c == ']' {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
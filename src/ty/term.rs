@@ -1,22 +1,32 @@
-//! Configuration for a `term`-type logger and corresponding factory for an
-//! `Async` drain.
+//! Configuration for a `term`-type logger and corresponding factories for an
+//! `Async` drain and a synchronous drain.
 //!
 //! Corresponds to a logger created with `slog_term::TermDecorator`.
 
-use Error;
-pub use common::{Level, OpenMode, TermTarget as Target, Timestamp};
+use {BoxDrain, Context, Error};
+use drain::{LevelFilter, Prioritize};
+use output::journal::{self, Priority, PriorityPrefix, Stream};
+pub use common::{
+    AsyncWorker,
+    Directives,
+    Journal,
+    Level,
+    OpenMode,
+    TermTarget as Target,
+    Timestamp,
+};
 
 use std;
 
-use slog::{Drain, Never};
+use slog::Drain;
 use slog_async::{Async, AsyncGuard};
-use slog_term::{CompactFormat, Decorator, FullFormat, TermDecorator};
+use slog_term::{CompactFormat, Decorator, FullFormat, PlainDecorator, TermDecorator};
 
 use chrono::{Local, Utc};
 
 
 /// Configuration for a logger of type `term`.
-#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Config {
     /// The target to which the logger should write.
     #[serde(default)]
@@ -26,17 +36,36 @@ pub struct Config {
     #[serde(default)]
     pub format: Format,
 
-    /// The minimal logging level the logger should output.
+    /// The minimal logging level the logger should output, optionally per
+    /// module.
     #[serde(default)]
-    pub level: Level,
+    pub level: Directives,
+
+    /// The name of the level handle attached to the logger, allowing its level
+    /// to be adjusted at runtime (see [`LevelHandle`](::drain::LevelHandle)).
+    /// The handle can be retrieved from the context the logger has been built
+    /// in (see [`Context::handle`](::Context::handle)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
 
     /// The timestamp format.
     #[serde(default)]
     pub timestamp: Timestamp,
 
+    /// Whether lines should be prefixed with their `sd-daemon` priority.
+    ///
+    /// While lines are prefixed, colors are disabled.
+    #[serde(default)]
+    pub journal: Journal,
+
     /// The color settings.
     #[serde(default)]
     pub color: Color,
+
+    /// The settings of the worker thread of the `Async` drain built by the
+    /// default factory.
+    #[serde(default, rename = "async")]
+    pub worker: AsyncWorker,
 }
 
 impl ::Config for Config {
@@ -95,12 +124,49 @@ impl ::Factory for Factory {
     type Target = (Async, AsyncGuard);
 
     fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
-        build(cfg)
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        Ok(cfg.worker.builder(build(cfg, ctx)?.fuse()).build_with_guard())
+    }
+}
+
+
+/// Factory for a synchronous drain of type `term`.
+pub struct DrainFactory;
+
+impl ::Factory for DrainFactory {
+    type Config = Config;
+    type Target = BoxDrain;
+
+    fn build(&self, cfg: &Config) -> Result<Self::Target, Error> {
+        self.build_in(cfg, &Context::default())
+    }
+
+    fn build_in(&self, cfg: &Config, ctx: &Context) -> Result<Self::Target, Error> {
+        build(cfg, ctx)
     }
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(trivially_copy_pass_by_ref))]
-fn build(cfg: &Config) -> Result<(Async, AsyncGuard), Error> {
+fn build(cfg: &Config, ctx: &Context) -> Result<BoxDrain, Error> {
+    let stream = match cfg.target {
+        Target::Stdout => Stream::Stdout,
+        Target::Stderr => Stream::Stderr,
+    };
+
+    if journal::is_enabled(cfg.journal, Some(stream)) {
+        let priority = Priority::new();
+
+        let output: Box<std::io::Write + Send> = match stream {
+            Stream::Stdout => Box::new(std::io::stdout()),
+            Stream::Stderr => Box::new(std::io::stderr()),
+        };
+
+        let output = PriorityPrefix::new(output, priority.clone());
+        return build_1(cfg, ctx, PlainDecorator::new(output), Some(priority));
+    }
+
     let builder = match cfg.target {
         Target::Stdout => TermDecorator::new().stdout(),
         Target::Stderr => TermDecorator::new().stderr(),
@@ -112,11 +178,10 @@ fn build(cfg: &Config) -> Result<(Async, AsyncGuard), Error> {
         Color::Force => builder.force_color(),
     };
 
-    build_1(cfg, builder.build())
+    build_1(cfg, ctx, builder.build(), None)
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(trivially_copy_pass_by_ref))]
-fn build_1<D>(cfg: &Config, decorator: D) -> Result<(Async, AsyncGuard), Error>
+fn build_1<D>(cfg: &Config, ctx: &Context, decorator: D, priority: Option<Priority>) -> Result<BoxDrain, Error>
 where
     D: Decorator + Send + 'static,
 {
@@ -129,13 +194,9 @@ where
                 Timestamp::Rfc3339Local => format.use_custom_timestamp(timestamp_iso8601_local),
             };
 
-            let format = format
-                .use_original_order()
-                .build()
-                .filter_level(cfg.level.into())
-                .fuse();
+            let format = format.use_original_order().build();
 
-            build_2(cfg, format)
+            build_2(cfg, ctx, format, priority)
         },
         Format::Compact => {
             let format = CompactFormat::new(decorator);
@@ -145,19 +206,28 @@ where
                 Timestamp::Rfc3339Local => format.use_custom_timestamp(timestamp_iso8601_local),
             };
 
-            let format = format.build().filter_level(cfg.level.into()).fuse();
+            let format = format.build();
 
-            build_2(cfg, format)
+            build_2(cfg, ctx, format, priority)
         },
     }
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(trivially_copy_pass_by_ref))]
-fn build_2<D>(_cfg: &Config, drain: D) -> Result<(Async, AsyncGuard), Error>
+fn build_2<D>(cfg: &Config, ctx: &Context, drain: D, priority: Option<Priority>) -> Result<BoxDrain, Error>
 where
-    D: Drain<Err = Never, Ok = ()> + Send + 'static,
+    D: Drain<Err = std::io::Error, Ok = ()> + Send + 'static,
 {
-    Ok(Async::new(drain).build_with_guard())
+    let drain: BoxDrain = match priority {
+        Some(priority) => Box::new(Prioritize::new(drain, priority)),
+        None => Box::new(drain),
+    };
+
+    let filter = LevelFilter::new(drain, cfg.level.clone());
+
+    match cfg.handle {
+        Some(ref name) => Ok(Box::new(filter.with_handle(ctx.handle(name)))),
+        None => Ok(Box::new(filter)),
+    }
 }
 
 fn timestamp_iso8601_utc(w: &mut std::io::Write) -> std::io::Result<()> {
@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use slog::{Drain, Logger};
+
+use slog_conf::Config;
+use slog_conf::binary::{self, Reader, Value};
+use slog_conf::common::Level;
+
+
+fn logger(path: &Path, encoding: &str) -> Logger {
+    let config = format!(
+        "type = \"binary\"\ntarget = {{ path = {:?}, mode = \"truncate\" }}\nencoding = \"{}\"",
+        path.to_str().unwrap(),
+        encoding
+    );
+
+    let config: Box<Config> = toml::from_str(&config).unwrap();
+    let drain = slog_conf::build_drain(config.as_ref()).unwrap();
+
+    Logger::root(Mutex::new(drain).fuse(), o!("logger" => "root"))
+}
+
+fn round_trip(encoding: &str) {
+    let path = std::env::temp_dir().join(format!(
+        "slog-conf-test-{}-{}.bin",
+        std::process::id(),
+        encoding
+    ));
+
+    {
+        let log = logger(&path, encoding);
+
+        info!(log, "first"; "bool" => true, "int" => -70000i64, "uint" => u64::MAX);
+        let none: Option<u32> = None;
+        warn!(log, #"tag", "second"; "float" => -2.5, "str" => "text", "none" => none);
+    }
+
+    let records = Reader::open(&path).unwrap().collect::<io::Result<Vec<_>>>().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(records.len(), 2);
+
+    assert_eq!(records[0].level, Level::Info);
+    assert_eq!(records[0].msg, "first");
+    assert_eq!(records[0].tag, "");
+    assert_eq!(records[0].kv, vec![
+        ("uint".to_owned(), Value::Uint(u64::MAX)),
+        ("int".to_owned(), Value::Int(-70000)),
+        ("bool".to_owned(), Value::Bool(true)),
+        ("logger".to_owned(), Value::Str("root".into())),
+    ]);
+
+    assert_eq!(records[1].level, Level::Warning);
+    assert_eq!(records[1].msg, "second");
+    assert_eq!(records[1].tag, "tag");
+    assert_eq!(records[1].kv, vec![
+        ("none".to_owned(), Value::Null),
+        ("str".to_owned(), Value::Str("text".into())),
+        ("float".to_owned(), Value::Float(-2.5)),
+        ("logger".to_owned(), Value::Str("root".into())),
+    ]);
+
+    assert!(records[0].time <= records[1].time);
+    assert_eq!(records[0].module, module_path!());
+    assert_eq!(records[0].file, file!());
+}
+
+/// Encodes a record with a `kv` entry consisting of `depth` nested arrays,
+/// using the specified header bytes of a map, a string, and an array with
+/// zero elements, as well as the encoding of a null value.
+fn nested(map: u8, string: u8, array: u8, null: u8, depth: usize) -> Vec<u8> {
+    let push_str = |payload: &mut Vec<u8>, value: &str| {
+        payload.push(string | value.len() as u8);
+        payload.extend_from_slice(value.as_bytes());
+    };
+
+    let mut payload = vec![map | 3];
+
+    push_str(&mut payload, "ts");
+    payload.push(0);
+
+    push_str(&mut payload, "level");
+    push_str(&mut payload, "INFO");
+
+    push_str(&mut payload, "kv");
+    payload.push(map | 1);
+    push_str(&mut payload, "a");
+    payload.extend(std::iter::repeat(array | 1).take(depth));
+    payload.push(null);
+
+    payload
+}
+
+fn msgpack(depth: usize) -> Vec<u8> {
+    nested(0x80, 0xa0, 0x90, 0xc0, depth)
+}
+
+fn cbor(depth: usize) -> Vec<u8> {
+    nested(0xa0, 0x60, 0x80, 0xf6, depth)
+}
+
+
+#[test]
+fn round_trips_msgpack() {
+    round_trip("msgpack");
+}
+
+#[test]
+fn round_trips_cbor() {
+    round_trip("cbor");
+}
+
+#[test]
+fn decodes_nested_values() {
+    for payload in &[msgpack(100), cbor(100)] {
+        let record = binary::decode(payload).unwrap();
+        assert_eq!(record.msg, "");
+        assert_eq!(record.kv.len(), 1);
+    }
+}
+
+#[test]
+fn rejects_deeply_nested_values() {
+    for payload in &[msgpack(100_000), cbor(100_000)] {
+        let error = binary::decode(payload).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("nested too deeply"));
+    }
+}
@@ -0,0 +1,139 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io::Read;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use slog::{Drain, Logger};
+
+use slog_conf::Config;
+
+
+/// A stand-in for Fluentd, accepting a single connection and collecting
+/// everything received via it.
+struct Fluentd {
+    port:     u16,
+    stream:   Arc<Mutex<Option<TcpStream>>>,
+    received: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Fluentd {
+    fn start(port: u16) -> Fluentd {
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stream = Arc::new(Mutex::new(None));
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let (accepted, buf) = (stream.clone(), received.clone());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            drop(listener);
+
+            *accepted.lock().unwrap() = Some(stream.try_clone().unwrap());
+
+            let mut chunk = [0; 4096];
+            while let Ok(len) = stream.read(&mut chunk) {
+                if len == 0 {
+                    break;
+                }
+
+                buf.lock().unwrap().extend_from_slice(&chunk[..len]);
+            }
+        });
+
+        Fluentd { port, stream, received }
+    }
+
+    /// Closes the connection, the listener has already been closed.
+    fn kill(self) {
+        if let Some(ref stream) = *self.stream.lock().unwrap() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Waits until `pattern` has been received and returns its position.
+    fn wait_for(&self, pattern: &str) -> usize {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        loop {
+            {
+                let received = self.received.lock().unwrap();
+                let found = received
+                    .windows(pattern.len())
+                    .position(|w| w == pattern.as_bytes());
+
+                if let Some(pos) = found {
+                    return pos;
+                }
+            }
+
+            assert!(Instant::now() < deadline, "{:?} has not been received", pattern);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+
+fn logger(port: u16) -> Logger {
+    let config = format!(
+        "type = \"fluent\"\ntarget = {{ tcp = \"127.0.0.1:{}\" }}\nbackoff = \"100ms\"",
+        port
+    );
+
+    let config: Box<Config> = toml::from_str(&config).unwrap();
+    let drain = slog_conf::build_drain(config.as_ref()).unwrap();
+
+    // the drain must never fail, `fuse` panics otherwise
+    Logger::root(Mutex::new(drain).fuse(), o!())
+}
+
+
+#[test]
+fn reconnects_and_reports_dropped_records() {
+    let fluentd = Fluentd::start(0);
+    let port = fluentd.port;
+    let log = logger(port);
+
+    info!(log, "before restart");
+    fluentd.wait_for("before restart");
+    fluentd.kill();
+
+    // writes may still succeed until the reset has been noticed
+    for _ in 0..10 {
+        info!(log, "lost");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let fluentd = Fluentd::start(port);
+    thread::sleep(Duration::from_millis(200));
+
+    info!(log, "after restart");
+
+    let report = fluentd.wait_for("dropped");
+    let after = fluentd.wait_for("after restart");
+    assert!(report < after);
+}
+
+#[test]
+fn builds_while_unreachable() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let log = logger(port);
+
+    info!(log, "lost");
+
+    let fluentd = Fluentd::start(port);
+    thread::sleep(Duration::from_millis(200));
+
+    info!(log, "reachable");
+
+    let report = fluentd.wait_for("dropped 1 records");
+    let reachable = fluentd.wait_for("reachable");
+    assert!(report < reachable);
+}
@@ -0,0 +1,201 @@
+#[macro_use]
+extern crate slog;
+extern crate slog_conf;
+
+extern crate toml;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use slog::{Drain, Logger};
+
+use slog_conf::Config;
+
+
+/// A stand-in for an HTTP server, passing the body of each request to the test
+/// and answering it with the next status code sent by the test.
+struct Server {
+    port:     u16,
+    requests: Receiver<String>,
+    statuses: Sender<u16>,
+}
+
+impl Server {
+    fn start() -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let (requests, rx_requests) = channel();
+        let (statuses, rx_statuses) = channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).unwrap();
+
+                if requests.send(String::from_utf8(body).unwrap()).is_err() {
+                    break;
+                }
+
+                let status: u16 = match rx_statuses.recv() {
+                    Ok(status) => status,
+                    Err(_) => break,
+                };
+
+                let response = format!("HTTP/1.1 {} -\r\nContent-Length: 0\r\n\r\n", status);
+                let _ = stream.get_mut().write_all(response.as_bytes());
+            }
+        });
+
+        Server {
+            port,
+            requests: rx_requests,
+            statuses,
+        }
+    }
+
+    fn respond(&self, status: u16) {
+        self.statuses.send(status).unwrap();
+    }
+
+    fn request(&self) -> String {
+        self.requests.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    fn no_request(&self) -> bool {
+        self.requests.recv_timeout(Duration::from_millis(200)).is_err()
+    }
+}
+
+
+fn logger(port: u16, options: &str) -> Logger {
+    logger_with_backoff(port, "10ms", options)
+}
+
+fn logger_with_backoff(port: u16, backoff: &str, options: &str) -> Logger {
+    let config = format!(
+        "type = \"http\"\nurl = \"http://127.0.0.1:{}/\"\nflush-interval = \"10ms\"\nbackoff = \"{}\"\n{}",
+        port, backoff, options
+    );
+
+    let config: Box<Config> = toml::from_str(&config).unwrap();
+    let drain = slog_conf::build_drain(config.as_ref()).unwrap();
+
+    // the drain must never fail, `fuse` panics otherwise
+    Logger::root(Mutex::new(drain).fuse(), o!())
+}
+
+
+#[test]
+fn retries_failed_requests() {
+    let server = Server::start();
+    let log = logger(server.port, "retries = 2");
+
+    info!(log, "retried");
+
+    server.respond(503);
+    assert!(server.request().contains("retried"));
+    server.respond(500);
+    assert!(server.request().contains("retried"));
+    server.respond(204);
+    assert!(server.request().contains("retried"));
+
+    assert!(server.no_request());
+}
+
+#[test]
+fn reports_batches_dropped_after_retries() {
+    let server = Server::start();
+    let log = logger(server.port, "retries = 1");
+
+    info!(log, "lost");
+
+    server.respond(503);
+    server.respond(503);
+    assert!(server.request().contains("lost"));
+    assert!(server.request().contains("lost"));
+
+    info!(log, "delivered");
+
+    server.respond(204);
+    let body = server.request();
+    let report = body.find("dropped 1 records").unwrap();
+    let delivered = body.find("delivered").unwrap();
+    assert!(body.contains("http status 503"));
+    assert!(report < delivered);
+}
+
+#[test]
+fn reports_records_dropped_while_queue_is_full() {
+    let server = Server::start();
+    let log = logger(server.port, "queue = 1\nbatch-size = 1");
+
+    // the first record is taken from the queue while the response is pending
+    info!(log, "sent");
+    assert!(server.request().contains("sent"));
+
+    info!(log, "queued");
+    info!(log, "lost");
+    info!(log, "lost");
+
+    server.respond(204);
+    assert!(server.request().contains("dropped 2 records"));
+    server.respond(204);
+    assert!(server.request().contains("queued"));
+    server.respond(204);
+
+    drop(log);
+    assert!(server.no_request());
+}
+
+#[test]
+fn stops_retrying_once_dropped() {
+    let server = Server::start();
+    let log = logger_with_backoff(server.port, "1h", "retries = 5\nbatch-size = 1");
+
+    info!(log, "failed");
+    info!(log, "lost");
+
+    server.respond(503);
+    assert!(server.request().contains("failed"));
+
+    // the worker must neither wait for the backoff nor send the records left
+    let start = Instant::now();
+    drop(log);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(server.no_request());
+}
+
+#[test]
+fn labels_loki_streams_by_level_by_default() {
+    let server = Server::start();
+    let log = logger(server.port, "format = \"loki\"");
+
+    info!(log, "labeled");
+
+    server.respond(204);
+    let body = server.request();
+    assert!(body.contains("\"stream\":{\"level\":\"INFO\"}"));
+    assert!(body.contains("labeled"));
+}